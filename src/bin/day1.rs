@@ -14,8 +14,9 @@
 /// let numbers: Vec<i64> = s.numbers().collect();
 /// assert_eq!(numbers, vec![1, 2, 3, 4]);
 /// ```
+use std::collections::VecDeque;
 use std::fs;
-use std::cmp;
+use std::sync::OnceLock;
 
 fn main() {
     let input = fs::read_to_string("inputs/1.txt").expect("Failed to read input file");
@@ -58,95 +59,218 @@ pub fn as_two_digit_number(digits: impl Iterator<Item = i64>) -> i64 {
     }
 }
 
+/// The spelled-out digits that `Numbers` recognizes.
+const NUMBER_WORDS: [(&str, i64); 10] = [
+    ("zero", 0),
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+/// The automaton for `NUMBER_WORDS`, built the first time it is needed.
+fn number_words() -> &'static AhoCorasick {
+    static AUTOMATON: OnceLock<AhoCorasick> = OnceLock::new();
+    AUTOMATON.get_or_init(|| AhoCorasick::new(&NUMBER_WORDS))
+}
+
+/// An Aho-Corasick automaton, which finds every occurrence of a set of
+/// patterns in a string in a single pass, overlapping ones included.
+///
+/// It is a trie of the patterns where each state also knows the longest
+/// proper suffix of its text that is in the trie. Following those links in
+/// advance turns it into a full transition table, so every byte of the input
+/// is one table lookup.
+pub struct AhoCorasick {
+    /// The next state for each state and byte, at `state * 256 + byte`.
+    transitions: Vec<u32>,
+    /// The patterns that end at each state, as their length and value,
+    /// longest first.
+    outputs: Vec<Vec<(usize, i64)>>,
+}
+
+/// An occurrence of a pattern, at `start..end` in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub value: i64,
+}
+
+impl AhoCorasick {
+    const ROOT: u32 = 0;
+
+    pub fn new(patterns: &[(&str, i64)]) -> Self {
+        const MISSING: u32 = u32::MAX;
+        let mut transitions = vec![MISSING; 256];
+        let mut outputs = vec![vec![]];
+
+        for &(pattern, value) in patterns {
+            let mut state = Self::ROOT;
+            for &byte in pattern.as_bytes() {
+                let index = state as usize * 256 + byte as usize;
+                if transitions[index] == MISSING {
+                    transitions[index] = outputs.len() as u32;
+                    transitions.extend([MISSING; 256]);
+                    outputs.push(vec![]);
+                }
+                state = transitions[index];
+            }
+            outputs[state as usize].push((pattern.len(), value));
+        }
+
+        // Breadth first, so the suffix link of a state is always done before it.
+        let mut suffix = vec![Self::ROOT; outputs.len()];
+        let mut queue = VecDeque::new();
+        for next in &mut transitions[..256] {
+            match *next {
+                MISSING => *next = Self::ROOT,
+                next => queue.push_back(next),
+            }
+        }
+        while let Some(state) = queue.pop_front() {
+            let inherited = outputs[suffix[state as usize] as usize].clone();
+            outputs[state as usize].extend(inherited);
+            for byte in 0..256 {
+                let index = state as usize * 256 + byte;
+                let fallback = transitions[suffix[state as usize] as usize * 256 + byte];
+                match transitions[index] {
+                    MISSING => transitions[index] = fallback,
+                    next => {
+                        suffix[next as usize] = fallback;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        Self {
+            transitions,
+            outputs,
+        }
+    }
+
+    fn step(&self, state: u32, byte: u8) -> u32 {
+        self.transitions[state as usize * 256 + byte as usize]
+    }
+
+    /// Returns every occurrence of every pattern, ordered by where they end.
+    pub fn find_overlapping<'a>(&'a self, haystack: &'a str) -> impl Iterator<Item = Match> + 'a {
+        haystack
+            .bytes()
+            .enumerate()
+            .scan(Self::ROOT, move |state, (i, byte)| {
+                *state = self.step(*state, byte);
+                Some((i + 1, &self.outputs[*state as usize]))
+            })
+            .flat_map(|(end, outputs)| {
+                outputs.iter().map(move |&(len, value)| Match {
+                    start: end - len,
+                    end,
+                    value,
+                })
+            })
+    }
+}
+
 /// Returns an iterator of the numbers in a string, where
 /// only the literal spelling of a number or a digit are allowed.
+/// Spelled numbers may overlap, so "threeight" returns `3, 8`.
 ///
 /// Example: "12_ABC_one7" will return `1, 2, 1, 7`
 pub struct Numbers<'a> {
-    // chars: Chars<'a>,
-    // buffer: String,
-    input: &'a str,
+    input: &'a [u8],
     pos: usize,
-    number_words: Vec<(&'static str, i64)>,
+    automaton: &'static AhoCorasick,
+    state: u32,
+    /// The spelled numbers ending at the previous byte that are not returned yet.
+    pending: &'static [(usize, i64)],
 }
 
 impl<'a> Numbers<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
-            input,
+            input: input.as_bytes(),
             pos: 0,
-            number_words: vec![
-                ("zero", 0),
-                ("one", 1),
-                ("two", 2),
-                ("three", 3),
-                ("four", 4),
-                ("five", 5),
-                ("six", 6),
-                ("seven", 7),
-                ("eight", 8),
-                ("nine", 9),
-            ],
-        }
-    }
-
-    pub fn parse_spelled_number(&mut self) -> Option<i64> {
-        let mut longest_match = (0, None); // (length of match, number)
-
-        for &(word, number) in &self.number_words {
-            if self.pos + word.len() <= self.input.len() && self.input[self.pos..].starts_with(word)
-            {
-                longest_match = cmp::max(longest_match, (word.len(), Some(number)));
-            }
-        }
-
-        if let (len, Some(number)) = longest_match {
-            self.pos += len - 1; // -1 so that overlapping "threeight" returns [3, 8]
-            return Some(number);
+            automaton: number_words(),
+            state: AhoCorasick::ROOT,
+            pending: &[],
         }
-
-        None
     }
 }
 
-impl<'a> Iterator for Numbers<'a> {
+impl Iterator for Numbers<'_> {
     type Item = i64;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.pos < self.input.len() {
-            if let Some(digit) = self.input[self.pos..]
-                .chars()
-                .next()
-                .filter(|c| c.is_digit(10))
-            {
-                self.pos += 1; // Advance position after finding a digit
-                return Some(digit.to_digit(10).unwrap() as i64);
-            }
-
-            if let Some(number) = self.parse_spelled_number() {
+        loop {
+            if let Some((&(_, number), rest)) = self.pending.split_first() {
+                self.pending = rest;
                 return Some(number);
             }
 
+            let &byte = self.input.get(self.pos)?;
             self.pos += 1;
+            self.state = self.automaton.step(self.state, byte);
+            if byte.is_ascii_digit() {
+                return Some((byte - b'0') as i64);
+            }
+            self.pending = &self.automaton.outputs[self.state as usize];
         }
-
-        None
     }
 }
 
 trait NumberIterExt {
-    fn numbers(&self) -> Numbers;
+    fn numbers(&self) -> Numbers<'_>;
 }
 
 impl NumberIterExt for str {
-    fn numbers(&self) -> Numbers {
+    fn numbers(&self) -> Numbers<'_> {
         Numbers::new(self)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use super::NumberIterExt;
+    use adventofcode2023::Rng;
+    use std::time::Instant;
+
+    use super::{number_words, AhoCorasick, Match, NumberIterExt, NUMBER_WORDS};
+
+    /// The numbers in a string found by checking every number word at every position.
+    fn naive_numbers(s: &str) -> Vec<i64> {
+        let mut numbers = vec![];
+        for (i, c) in s.char_indices() {
+            if let Some(digit) = c.to_digit(10) {
+                numbers.push(digit as i64);
+            }
+            for (word, number) in NUMBER_WORDS {
+                if s[i..].starts_with(word) {
+                    numbers.push(number);
+                }
+            }
+        }
+        numbers
+    }
+
+    /// A calibration document of `lines` random lines.
+    fn calibration_document(seed: u64, lines: usize) -> String {
+        const ALPHABET: &[u8] = b"onetwhrfuivsxegnz0123456789abcdklmpq";
+        let mut rng = Rng::new(seed);
+        let mut document = String::new();
+        for _ in 0..lines {
+            for _ in 0..20 + rng.below(40) {
+                document.push(ALPHABET[rng.below(ALPHABET.len())] as char);
+            }
+            document.push('\n');
+        }
+        document
+    }
 
     /// The example given for part one from `https://adventofcode.com/2023/day/1`.
     #[test]
@@ -185,4 +309,81 @@ pub mod tests {
         let numbers: Vec<i64> = s.numbers().collect();
         assert_eq!(numbers, vec![3, 8, 1]);
     }
+
+    #[test]
+    fn numbers_unicode() {
+        let numbers: Vec<i64> = "ünë2öneone".numbers().collect();
+        assert_eq!(numbers, vec![2, 1]);
+    }
+
+    #[test]
+    fn aho_corasick_overlapping() {
+        let automaton = AhoCorasick::new(&[("he", 0), ("she", 1), ("his", 2), ("hers", 3)]);
+        let matches: Vec<Match> = automaton.find_overlapping("ushers").collect();
+        assert_eq!(
+            matches,
+            vec![
+                Match {
+                    start: 1,
+                    end: 4,
+                    value: 1
+                },
+                Match {
+                    start: 2,
+                    end: 4,
+                    value: 0
+                },
+                Match {
+                    start: 2,
+                    end: 6,
+                    value: 3
+                },
+            ]
+        );
+
+        let matches: Vec<i64> = number_words()
+            .find_overlapping("sevenineightwone")
+            .map(|m| m.value)
+            .collect();
+        assert_eq!(matches, vec![7, 9, 8, 2, 1]);
+    }
+
+    #[test]
+    fn numbers_match_naive() {
+        let document = calibration_document(1, 2000);
+        for line in document.lines() {
+            assert_eq!(
+                line.numbers().collect::<Vec<_>>(),
+                naive_numbers(line),
+                "{line}"
+            );
+        }
+    }
+
+    /// Compares the automaton with the naive scan on an 8 MB document.
+    #[test]
+    #[ignore = "slow in debug builds, run with `cargo test --release -- --ignored --nocapture`"]
+    fn numbers_performance() {
+        let document = calibration_document(2, 200_000);
+        assert!(document.len() > 8_000_000);
+
+        let start = Instant::now();
+        let expected: i64 = document
+            .lines()
+            .map(|line| super::as_two_digit_number(naive_numbers(line).into_iter()))
+            .sum();
+        let naive = start.elapsed();
+
+        let start = Instant::now();
+        let sum = super::part_two(&document);
+        let automaton = start.elapsed();
+
+        assert_eq!(sum, expected);
+        let megabytes = document.len() as f64 / 1_000_000.0;
+        println!(
+            "{megabytes:.1} MB: naive {naive:?} ({:.0} MB/s), Aho-Corasick {automaton:?} ({:.0} MB/s)",
+            megabytes / naive.as_secs_f64(),
+            megabytes / automaton.as_secs_f64()
+        );
+    }
 }
@@ -1,4 +1,7 @@
+use adventofcode2023::Rng;
+use std::env;
 use std::fs;
+use std::process;
 use array2d::Array2D;
 
 pub const NORTH: u8 = 0b1000;
@@ -7,6 +10,26 @@ pub const SOUTH: u8 = 0b0010;
 pub const WEST:  u8 = 0b0001;
 
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("--generate") {
+        let usage = "usage: day10 --generate SEED ROWS COLS, where ROWS and COLS are at least 4";
+        let maze = match &args[1..] {
+            [seed, rows, cols] => match (seed.parse(), rows.parse(), cols.parse()) {
+                (Ok(seed), Ok(rows @ 4..), Ok(cols @ 4..)) => Some(generate(seed, rows, cols)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(maze) = maze else {
+            eprintln!("{usage}");
+            process::exit(2);
+        };
+        print!("{}", maze.text);
+        eprintln!("Farthest: {}", maze.farthest);
+        eprintln!("Enclosed: {}", maze.enclosed);
+        return;
+    }
+
     let input = fs::read_to_string("inputs/10.txt").expect("Failed to read input file");
     println!("Part one: {}", part_one(&input));
     println!("Part two: {}", part_two(&input));
@@ -36,7 +59,7 @@ pub fn part_one(input: &str) -> usize {
                 next_points.push((row, col + 1));
             }
         }
-        if next_points.len() == 0 {
+        if next_points.is_empty() {
             break;
         }
         points = next_points;
@@ -109,9 +132,163 @@ pub fn parse(input: &str) -> (Array2D<u8>, (usize, usize)) {
     (grid, start)
 }
 
+/// A randomly generated puzzle input together with its known answers.
+pub struct Maze {
+    pub text: String,
+    /// The number of steps from `S` to the farthest point of the loop.
+    pub farthest: usize,
+    /// The number of tiles enclosed by the loop.
+    pub enclosed: usize,
+}
+
+/// Generates a random `rows` by `cols` maze containing a single closed
+/// loop through `S`, with every other tile filled with junk pipes.
+///
+/// The loop is the outline of a randomly grown polyomino whose squares
+/// have tile centers as their corners. As long as the polyomino has no
+/// holes and no two squares touch only at a corner, its outline is a
+/// simple closed path, and Pick's theorem gives the enclosed tile count
+/// from the polyomino's area and the length of the loop.
+pub fn generate(seed: u64, rows: usize, cols: usize) -> Maze {
+    assert!(rows >= 4 && cols >= 4, "maze must be at least 4x4");
+
+    let mut rng = Rng::new(seed);
+
+    // Squares are indexed by their top left corner, and keep a margin
+    // of one tile so that the loop never touches the edge of the grid.
+    let mut squares = Array2D::filled_with(false, rows, cols);
+    let target = 1 + rng.below((rows - 3) * (cols - 3) / 2 + 1);
+    let first = (1 + rng.below(rows - 3), 1 + rng.below(cols - 3));
+    let mut area = 0;
+    let mut frontier = vec![first];
+
+    while area < target && !frontier.is_empty() {
+        let (row, col) = frontier.swap_remove(rng.below(frontier.len()));
+        if squares[(row, col)] || (area > 0 && !can_grow(&squares, row, col)) {
+            continue;
+        }
+        squares[(row, col)] = true;
+        area += 1;
+        for (r, c) in [
+            (row - 1, col),
+            (row + 1, col),
+            (row, col - 1),
+            (row, col + 1),
+        ] {
+            if r >= 1 && r < rows - 2 && c >= 1 && c < cols - 2 && !squares[(r, c)] {
+                frontier.push((r, c));
+            }
+        }
+    }
+
+    // Every side of a square that borders the outside is a loop segment
+    // between the two tiles at the ends of that side.
+    let mut grid = Array2D::filled_with(0u8, rows, cols);
+    let mut length = 0;
+    for row in 1..rows - 2 {
+        for col in 1..cols - 2 {
+            if !squares[(row, col)] {
+                continue;
+            }
+            if !squares[(row - 1, col)] {
+                grid[(row, col)] |= EAST;
+                grid[(row, col + 1)] |= WEST;
+                length += 1;
+            }
+            if !squares[(row + 1, col)] {
+                grid[(row + 1, col)] |= EAST;
+                grid[(row + 1, col + 1)] |= WEST;
+                length += 1;
+            }
+            if !squares[(row, col - 1)] {
+                grid[(row, col)] |= SOUTH;
+                grid[(row + 1, col)] |= NORTH;
+                length += 1;
+            }
+            if !squares[(row, col + 1)] {
+                grid[(row, col + 1)] |= SOUTH;
+                grid[(row + 1, col + 1)] |= NORTH;
+                length += 1;
+            }
+        }
+    }
+
+    let on_loop: Vec<(usize, usize)> = grid
+        .indices_row_major()
+        .filter(|&index| grid[index] != 0)
+        .collect();
+    let start = on_loop[rng.below(on_loop.len())];
+
+    // Junk next to the start must not point at it, otherwise `parse`
+    // would connect it to the tile under `S`.
+    const JUNK: [u8; 7] = [0b1010, 0b0101, 0b1100, 0b1001, 0b0011, 0b0110, 0];
+    let mut text = String::with_capacity(rows * (cols + 1));
+    for row in 0..rows {
+        for col in 0..cols {
+            let tile = if (row, col) == start {
+                None
+            } else if grid[(row, col)] != 0 {
+                Some(grid[(row, col)])
+            } else {
+                loop {
+                    let tile = JUNK[rng.below(JUNK.len())];
+                    let points_at_start = (tile & NORTH > 0 && (row.wrapping_sub(1), col) == start)
+                        || (tile & SOUTH > 0 && (row + 1, col) == start)
+                        || (tile & WEST > 0 && (row, col.wrapping_sub(1)) == start)
+                        || (tile & EAST > 0 && (row, col + 1) == start);
+                    if !points_at_start {
+                        break Some(tile);
+                    }
+                }
+            };
+            text.push(tile.map_or('S', tile_char));
+        }
+        text.push('\n');
+    }
+
+    Maze {
+        text,
+        farthest: length / 2,
+        enclosed: area + 1 - length / 2,
+    }
+}
+
+/// Returns true if the square can be added to the polyomino without
+/// creating a hole or two squares that only touch at a corner.
+///
+/// This holds when the occupied squares around it form a single run
+/// that includes at least one of its four direct neighbors.
+fn can_grow(squares: &Array2D<bool>, row: usize, col: usize) -> bool {
+    let ring = [
+        squares[(row - 1, col)],
+        squares[(row - 1, col + 1)],
+        squares[(row, col + 1)],
+        squares[(row + 1, col + 1)],
+        squares[(row + 1, col)],
+        squares[(row + 1, col - 1)],
+        squares[(row, col - 1)],
+        squares[(row - 1, col - 1)],
+    ];
+    let transitions = (0..8).filter(|&i| ring[i] != ring[(i + 1) % 8]).count();
+    transitions == 2 && (ring[0] || ring[2] || ring[4] || ring[6])
+}
+
+pub fn tile_char(tile: u8) -> char {
+    match tile {
+        0b1010 => '|',
+        0b0101 => '-',
+        0b1100 => 'L',
+        0b1001 => 'J',
+        0b0011 => '7',
+        0b0110 => 'F',
+        _ => '.',
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{part_one};
+    use super::{generate, parse, part_one, EAST, NORTH, SOUTH, WEST};
+    use array2d::Array2D;
 
     #[test]
     fn test_example_1() {
@@ -136,4 +313,67 @@ mod tests {
         );
         assert_eq!(part_one(input), 8);
     }
+
+    #[test]
+    fn test_generated_small() {
+        for seed in 0..200 {
+            let maze = generate(seed, 4 + seed as usize % 17, 4 + seed as usize % 23);
+            assert_eq!(part_one(&maze.text), maze.farthest, "seed {seed}");
+            assert_eq!(count_enclosed(&maze.text), maze.enclosed, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_generated_is_deterministic() {
+        assert_eq!(generate(42, 30, 40).text, generate(42, 30, 40).text);
+    }
+
+    #[test]
+    #[ignore = "slow in debug builds, run with `cargo test --release -- --ignored`"]
+    fn test_generated_large() {
+        let maze = generate(2023, 2000, 2000);
+        assert_eq!(part_one(&maze.text), maze.farthest);
+        assert_eq!(count_enclosed(&maze.text), maze.enclosed);
+    }
+
+    /// Counts the enclosed tiles independently of the generator by
+    /// walking the loop from `S`, then scanning each row and toggling
+    /// whenever a loop tile with a northward pipe is crossed.
+    fn count_enclosed(input: &str) -> usize {
+        let (grid, start) = parse(input);
+        let mut on_loop = Array2D::filled_with(false, grid.num_rows(), grid.num_columns());
+        let (mut pos, mut from) = (start, 0);
+        loop {
+            on_loop[pos] = true;
+            let (row, col) = pos;
+            let tile = grid[pos] & !from;
+            (pos, from) = if tile & NORTH > 0 {
+                ((row - 1, col), SOUTH)
+            } else if tile & SOUTH > 0 {
+                ((row + 1, col), NORTH)
+            } else if tile & WEST > 0 {
+                ((row, col - 1), EAST)
+            } else {
+                ((row, col + 1), WEST)
+            };
+            if pos == start {
+                break;
+            }
+        }
+
+        let mut count = 0;
+        for row in 0..grid.num_rows() {
+            let mut inside = false;
+            for col in 0..grid.num_columns() {
+                if on_loop[(row, col)] {
+                    if grid[(row, col)] & NORTH > 0 {
+                        inside = !inside;
+                    }
+                } else if inside {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
 }
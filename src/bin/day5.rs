@@ -1,77 +1,278 @@
+use adventofcode2023::RangeSet;
+use anyhow::Context;
 use itertools::Itertools;
-use regex::Regex;
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Write};
 use std::ops::Range;
 use std::str::FromStr;
 use std::fs;
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     let input = fs::read_to_string("inputs/5.txt").expect("Failed to read input file");
-    println!("Part one: {}", part_one(&input));
-    println!("Part two: {}", part_two(&input));
+    for issue in validate(&input) {
+        eprintln!("WARNING: {issue}");
+    }
+
+    // `--ascii [START LEN]` or `--svg FILE [START LEN]` draws the tables, and traces
+    // the seeds from the input through them, or the range from START to START + LEN.
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some(mode @ ("--ascii" | "--svg")) = args.first().map(String::as_str) {
+        let usage = "usage: day5 [--ascii [START LEN] | --svg FILE [START LEN]]";
+        let (path, rest) = match (mode, &args[1..]) {
+            ("--svg", [path, rest @ ..]) => (Some(path), rest),
+            ("--svg", []) => anyhow::bail!("expected a path for the SVG file\n{usage}"),
+            (_, rest) => (None, rest),
+        };
+        let seeds = match rest {
+            [] => parse_seeds_part_two(input.lines().next().unwrap_or_default())?,
+            [start, len] => {
+                let start: i64 = start.parse().context("failed to parse START")?;
+                let len: i64 = len.parse().context("failed to parse LEN")?;
+                // The same checks as for the seed ranges in the input.
+                if len <= 0 {
+                    anyhow::bail!("expected a positive LEN\n{usage}");
+                }
+                let end = start.checked_add(len).with_context(|| {
+                    format!("expected START + LEN to be at most {}\n{usage}", i64::MAX)
+                })?;
+                RangeSet::from(start..end)
+            }
+            [_] => anyhow::bail!("expected a LEN after START\n{usage}"),
+            _ => anyhow::bail!("too many arguments\n{usage}"),
+        };
+
+        let almanac = input.parse::<Almanac>()?;
+        let tables = almanac
+            .path("seed", "location")
+            .ok_or_else(|| AlmanacError::MissingLink("seed".into(), "location".into()))?;
+
+        match path {
+            Some(path) => fs::write(path, render_svg(&tables, &seeds))
+                .with_context(|| format!("Could not write file: {path}"))?,
+            None => print!("{}", render_ascii(&tables, &seeds, 72)),
+        }
+        return Ok(());
+    }
+
+    println!("Part one: {}", part_one(&input)?);
+    println!("Part two: {}", part_two(&input)?);
+    Ok(())
 }
 
 
-pub fn part_one(input: &str) -> i64 {
-    let tables = parse_transition_tables(input).expect("failed to parse transition tables");
+pub fn part_one(input: &str) -> Result<i64, AlmanacError> {
+    let seeds = parse_seeds_part_one(input.lines().next().unwrap_or_default())?;
 
-    let seeds = parse_seeds_part_one(input.lines().next().expect("expected first line"))
-        .expect("failed to parse seeds");
+    let table = input.parse::<Almanac>()?.conversion("seed", "location")?;
 
-    seeds
-        .iter()
-        .map(|seed| tables.iter().fold(*seed, |acc, table| table.forward(acc)))
-        .min()
-        .expect("expected at least one seed")
+    // There is always at least one seed, or parsing would have failed.
+    Ok(seeds.iter().map(|&seed| table.forward(seed)).min().unwrap())
 }
 
-pub fn part_two(input: &str) -> i64 {
-    let tables = parse_transition_tables(input).expect("failed to parse transition tables");
+pub fn part_two(input: &str) -> Result<i64, AlmanacError> {
+    let seed_ranges = parse_seeds_part_two(input.lines().next().unwrap_or_default())?;
 
-    let seed_ranges = parse_seeds_part_two(input.lines().next().expect("expected first line"))
-        .expect("failed to parse seeds");
+    let table = input.parse::<Almanac>()?.conversion("seed", "location")?;
 
-    let rev: Vec<Vec<RangePair>> = tables
-        .iter()
-        .rev()
-        .map(|table| table.matches.iter().map(RangePair::flip).collect())
-        .collect();
+    // There is always at least one non-empty range, or parsing would have failed.
+    Ok(table.forward_ranges(&seed_ranges).first().unwrap())
+}
+
+/// Composes a chain of tables into a single table that maps
+/// directly from the first source to the last destination.
+pub fn collapse(tables: &[&Table]) -> Table {
+    let mut tables = tables.iter();
+    let first = tables
+        .next()
+        .map_or_else(Table::default, |table| table.normalize());
+    tables.fold(first, |acc, table| acc.compose(table))
+}
+
+/// The almanac is a graph of categories, such as `seed` or `soil`,
+/// where each table is an edge that converts numbers from its source
+/// category to its destination category.
+///
+/// Every category has at most one table converting out of it, so there
+/// is at most one way to get from one category to another.
+pub struct Almanac {
+    tables: Vec<Table>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AlmanacError {
+    Parse(ParseError),
+    /// Two tables convert out of the same category.
+    Duplicate(String),
+    /// Following the tables leads back to the category in the list.
+    Cycle(Vec<String>),
+    /// There is no chain of tables between the two categories.
+    MissingLink(String, String),
+    /// The chain exists in the other direction, but can't be undone.
+    NotInvertible(String, String),
+}
 
-    (0..)
-        .find(|&loc| {
-            let seed = rev.iter().fold(loc, |acc, ranges| {
-                ranges
-                    .iter()
-                    .find(|range| range.src.contains(&acc))
-                    .map_or(acc, |range| range.translate(acc))
-            });
-            seed_ranges
+impl From<ParseError> for AlmanacError {
+    fn from(err: ParseError) -> Self {
+        AlmanacError::Parse(err)
+    }
+}
+
+impl fmt::Display for AlmanacError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlmanacError::Parse(err) => write!(f, "{err}"),
+            AlmanacError::Duplicate(category) => {
+                write!(f, "more than one map converts from `{category}`")
+            }
+            AlmanacError::Cycle(path) => write!(f, "the maps form a cycle: {}", path.join(" -> ")),
+            AlmanacError::MissingLink(from, to) => {
+                write!(f, "no chain of maps converts `{from}` to `{to}`")
+            }
+            AlmanacError::NotInvertible(from, to) => {
+                write!(f, "the maps from `{to}` to `{from}` can't be reversed")
+            }
+        }
+    }
+}
+
+impl Error for AlmanacError {}
+
+impl Almanac {
+    pub fn new(tables: Vec<Table>) -> Result<Self, AlmanacError> {
+        let almanac = Self { tables };
+
+        for (i, table) in almanac.tables.iter().enumerate() {
+            if almanac.tables[..i]
                 .iter()
-                .any(|seed_range| seed_range.contains(&seed))
-        })
-        .unwrap()
+                .any(|other| other.source == table.source)
+            {
+                return Err(AlmanacError::Duplicate(table.source.clone()));
+            }
+        }
+
+        for table in &almanac.tables {
+            let mut path = vec![table.source.as_str()];
+            while let Some(next) = almanac.next(path.last().unwrap()) {
+                let seen = path.contains(&next.destination.as_str());
+                path.push(&next.destination);
+                if seen {
+                    return Err(AlmanacError::Cycle(
+                        path.into_iter().map(String::from).collect(),
+                    ));
+                }
+            }
+        }
+
+        Ok(almanac)
+    }
+
+    /// The table that converts out of the category.
+    fn next(&self, category: &str) -> Option<&Table> {
+        self.tables.iter().find(|table| table.source == category)
+    }
+
+    /// The chain of tables that leads from one category to another.
+    fn path(&self, from: &str, to: &str) -> Option<Vec<&Table>> {
+        let mut path = vec![];
+        let mut category = from;
+        while category != to {
+            let table = self.next(category)?;
+            category = &table.destination;
+            path.push(table);
+        }
+        Some(path)
+    }
+
+    /// Returns a single table that converts numbers from one category
+    /// to another, following the tables backwards if necessary, such as
+    /// from `location` to `seed`.
+    pub fn conversion(&self, from: &str, to: &str) -> Result<Table, AlmanacError> {
+        if let Some(path) = self.path(from, to) {
+            let mut table = collapse(&path);
+            table.source = from.to_string();
+            table.destination = to.to_string();
+            return Ok(table);
+        }
+
+        let path = self
+            .path(to, from)
+            .ok_or_else(|| AlmanacError::MissingLink(from.into(), to.into()))?;
+        collapse(&path)
+            .invert()
+            .ok_or_else(|| AlmanacError::NotInvertible(from.into(), to.into()))
+    }
+}
+
+impl FromStr for Almanac {
+    type Err = AlmanacError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Almanac::new(parse_transition_tables(s)?)
+    }
 }
 
 /// A range pair is a mapping from a source range
 /// to a destination range.
+#[derive(Debug, Clone, PartialEq)]
 pub struct RangePair {
     src: Range<i64>,
     dst: Range<i64>,
 }
 
 impl RangePair {
-    fn flip(&self) -> Self {
+    fn new(src: Range<i64>, offset: i64) -> Self {
         Self {
-            src: self.dst.clone(),
-            dst: self.src.clone(),
+            dst: src.start + offset..src.end + offset,
+            src,
         }
     }
 
+    /// The amount that is added to a number in the source range.
+    fn offset(&self) -> i64 {
+        self.dst.start - self.src.start
+    }
+
     fn contains(&self, num: i64) -> bool {
         self.src.contains(&num)
     }
 
     fn translate(&self, num: i64) -> i64 {
-        self.dst.start + num - self.src.start
+        num + self.offset()
+    }
+
+    /// Splits the set into the numbers that are in the source range,
+    /// already moved to the destination, and the ones that aren't.
+    fn forward_set(&self, set: &RangeSet<i64>) -> (RangeSet<i64>, RangeSet<i64>) {
+        let src = RangeSet::from(self.src.clone());
+        let moved = set
+            .intersection(&src)
+            .iter()
+            .map(|range| self.translate(range.start)..self.translate(range.end))
+            .collect();
+        (moved, set.difference(&src))
+    }
+
+    /// Parses a line of the format `dst src len`,
+    /// where `line` is the line number for errors.
+    fn parse(s: &str, line: usize) -> Result<Self, ParseError> {
+        let mut tokens = tokens(s);
+        let (_, _, dst) = next_number(&mut tokens, s, line, "the `dst` field")?;
+        let (_, _, src) = next_number(&mut tokens, s, line, "the `src` field")?;
+        let (column, token, len) = next_number(&mut tokens, s, line, "the `len` field")?;
+        if let Some((column, token)) = tokens.next() {
+            return Err(ParseError::new(line, column, "the end of the line", token));
+        }
+
+        let end = |start: i64| {
+            start.checked_add(len).ok_or_else(|| {
+                ParseError::new(line, column, "a `len` that ends before i64::MAX", token)
+            })
+        };
+        Ok(Self {
+            src: src..end(src)?,
+            dst: dst..end(dst)?,
+        })
     }
 }
 
@@ -79,25 +280,7 @@ impl FromStr for RangePair {
     type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s
-            .split_whitespace()
-            .map(|x| x.parse().map_err(|_| ParseError::ParseInt));
-
-        let dst = parts
-            .next()
-            .ok_or(ParseError::Format("Expected `dst` field".into()))??;
-        let src = parts
-            .next()
-            .ok_or(ParseError::Format("Expected `src` field".into()))??;
-        let len = parts
-            .next()
-            .ok_or(ParseError::Format("Expected `len` field".into()))??;
-        assert_eq!(None, parts.next());
-
-        Ok(Self {
-            src: src..src + len,
-            dst: dst..dst + len,
-        })
+        RangePair::parse(s, 1)
     }
 }
 
@@ -108,7 +291,10 @@ impl FromStr for RangePair {
 ///     `[0..10] => [50..60]`
 ///     `[20..25] => [80..85]`
 ///     `_ => x`
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Table {
+    source: String,
+    destination: String,
     matches: Vec<RangePair>,
 }
 
@@ -125,70 +311,657 @@ impl Table {
 
         // self.matches.iter().fold(x, |acc, range_pair| range_pair.translate(acc))
     }
+
+    /// Maps every number in the set at once, splitting each range
+    /// wherever it crosses the boundary of a match. The pieces that
+    /// don't fall into any match are passed through unchanged.
+    fn forward_ranges(&self, ranges: &RangeSet<i64>) -> RangeSet<i64> {
+        let mut result = RangeSet::new();
+        let mut pending = ranges.clone();
+        for range_pair in &self.matches {
+            let (moved, unmatched) = range_pair.forward_set(&pending);
+            result.merge(&moved);
+            pending = unmatched;
+        }
+        result.merge(&pending);
+        result
+    }
+
+    /// Returns an equivalent table whose matches are sorted and don't
+    /// overlap. Where the original matches overlap, the first one wins,
+    /// just like in `Table::forward`. Adjacent matches with the same
+    /// offset are merged, and matches that map a range onto itself are
+    /// dropped, since that is already what happens outside the matches.
+    pub fn normalize(&self) -> Table {
+        let mut pieces = vec![];
+        let mut claimed = RangeSet::new();
+        for range_pair in &self.matches {
+            let unclaimed = RangeSet::from(range_pair.src.clone()).difference(&claimed);
+            for piece in unclaimed {
+                pieces.push(RangePair::new(piece, range_pair.offset()));
+            }
+            claimed.insert(range_pair.src.clone());
+        }
+        self.with_pieces(pieces)
+    }
+
+    /// Builds a normalized table with the same categories from
+    /// matches that don't overlap.
+    fn with_pieces(&self, mut pieces: Vec<RangePair>) -> Table {
+        pieces.retain(|piece| !piece.src.is_empty() && piece.offset() != 0);
+        pieces.sort_by_key(|piece| piece.src.start);
+
+        let mut matches: Vec<RangePair> = vec![];
+        for piece in pieces {
+            match matches.last_mut() {
+                Some(last)
+                    if last.src.end == piece.src.start && last.offset() == piece.offset() =>
+                {
+                    last.src.end = piece.src.end;
+                    last.dst.end = piece.dst.end;
+                }
+                _ => matches.push(piece),
+            }
+        }
+        Table {
+            source: self.source.clone(),
+            destination: self.destination.clone(),
+            matches,
+        }
+    }
+
+    /// Splits the range into consecutive pieces at the boundaries of the
+    /// matches, paired with the offset that applies to each piece.
+    ///
+    /// Note: The table is expected to be normalized.
+    fn segments(&self, range: Range<i64>) -> Vec<(Range<i64>, i64)> {
+        let mut result = vec![];
+        let mut start = range.start;
+        for range_pair in &self.matches {
+            if range_pair.src.end <= start {
+                continue;
+            }
+            if range.end <= range_pair.src.start {
+                break;
+            }
+            if start < range_pair.src.start {
+                result.push((start..range_pair.src.start, 0));
+                start = range_pair.src.start;
+            }
+            let end = range.end.min(range_pair.src.end);
+            result.push((start..end, range_pair.offset()));
+            start = end;
+        }
+        if start < range.end {
+            result.push((start..range.end, 0));
+        }
+        result
+    }
+
+    /// Returns a table that maps each number through this table and
+    /// then through `other`, as a single normalized table.
+    pub fn compose(&self, other: &Table) -> Table {
+        let first = self.normalize();
+        let second = other.normalize();
+        let mut pieces = vec![];
+
+        // Numbers that this table moves land somewhere in `other`.
+        for range_pair in &first.matches {
+            let offset = range_pair.offset();
+            for (image, other_offset) in second.segments(range_pair.dst.clone()) {
+                let src = image.start - offset..image.end - offset;
+                pieces.push(RangePair::new(src, offset + other_offset));
+            }
+        }
+
+        // Numbers that this table leaves alone are only moved by `other`.
+        let moved: RangeSet<i64> = first.matches.iter().map(|m| m.src.clone()).collect();
+        for range_pair in &second.matches {
+            for gap in RangeSet::from(range_pair.src.clone()).difference(&moved) {
+                pieces.push(RangePair::new(gap, range_pair.offset()));
+            }
+        }
+
+        let mut table = self.with_pieces(pieces);
+        table.destination = other.destination.clone();
+        table
+    }
+
+    /// Returns the table that undoes this one, or `None` if the table
+    /// is not a one-to-one mapping of all numbers.
+    ///
+    /// Numbers outside the matches map to themselves, so the inverse only
+    /// exists when the matches never send two numbers to the same place,
+    /// and when the destination ranges cover exactly the source ranges.
+    /// Otherwise a number in a gap would collide with a destination, or a
+    /// source number would be left without anything mapping onto it.
+    pub fn invert(&self) -> Option<Table> {
+        let table = self.normalize();
+
+        // The source ranges of a normalized table never overlap, so the
+        // destination ranges don't either if they cover as many numbers.
+        let src: RangeSet<i64> = table.matches.iter().map(|m| m.src.clone()).collect();
+        let dst: RangeSet<i64> = table.matches.iter().map(|m| m.dst.clone()).collect();
+        if src.total_len() != dst.total_len() || src != dst {
+            return None;
+        }
+
+        let pieces = table
+            .matches
+            .iter()
+            .map(|m| RangePair::new(m.dst.clone(), -m.offset()))
+            .collect();
+        let mut inverse = table.with_pieces(pieces);
+        std::mem::swap(&mut inverse.source, &mut inverse.destination);
+        Some(inverse)
+    }
+
+    /// Parses a table from its lines, where `first_line` is the
+    /// line number of the header for errors.
+    ///
+    /// Note: The input is expected to start with a line of the format
+    /// `seed-to-soil map:`, which names the source and destination.
+    fn parse(s: &str, first_line: usize) -> Result<Self, ParseError> {
+        let mut lines = s.lines();
+        let header = lines.next().unwrap_or_default();
+        let (source, destination) = header
+            .trim()
+            .strip_suffix(" map:")
+            .and_then(|name| name.split_once("-to-"))
+            .ok_or_else(|| {
+                let expected = "a header of the form `X-to-Y map:`";
+                match tokens(header).next() {
+                    Some((column, token)) => ParseError::new(first_line, column, expected, token),
+                    None => ParseError::end_of_line(first_line, header, expected),
+                }
+            })?;
+
+        let mut matches = vec![];
+        for (i, line) in lines.enumerate() {
+            matches.push(RangePair::parse(line, first_line + 1 + i)?);
+        }
+        Ok(Self {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            matches,
+        })
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.source.is_empty() {
+            writeln!(f, "{}-to-{} map:", self.source, self.destination)?;
+        }
+        for range_pair in &self.matches {
+            writeln!(f, "[{:?}] => [{:?}]", range_pair.src, range_pair.dst)?;
+        }
+        write!(f, "_ => x")
+    }
 }
 
 impl FromStr for Table {
     type Err = ParseError;
 
-    /// Note: The input is expected to include a line of the format
-    /// `seed-to-soil map:`, but the implementation currently just
-    /// ignores the first line.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut matches = vec![];
-        for line in s.lines().skip(1) {
-            matches.push(line.parse()?);
-        }
-        Ok(Self { matches })
+        Table::parse(s, 1)
     }
 }
 
+/// An error while parsing the almanac, which points at the
+/// offending token and says what was expected instead.
 #[derive(Debug, PartialEq)]
-pub enum ParseError {
-    Format(String),
-    ParseInt,
-    Regex,
+pub struct ParseError {
+    /// The line of the error, starting from 1.
+    pub line: usize,
+    /// The column of the offending token, starting from 1.
+    pub column: usize,
+    /// A description of what should have been there, such as "the `src` field".
+    pub expected: &'static str,
+    /// The offending token, or `None` if the line ended too early.
+    pub found: Option<String>,
+}
+
+impl ParseError {
+    fn new(line: usize, column: usize, expected: &'static str, found: &str) -> Self {
+        Self {
+            line,
+            column,
+            expected,
+            found: Some(found.to_string()),
+        }
+    }
+
+    fn end_of_line(line: usize, s: &str, expected: &'static str) -> Self {
+        Self {
+            line,
+            column: s.chars().count() + 1,
+            expected,
+            found: None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: expected {}, ",
+            self.line, self.column, self.expected
+        )?;
+        match &self.found {
+            Some(token) => write!(f, "found `{token}`"),
+            None => write!(f, "found the end of the line"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Splits a line by whitespace, pairing each token with its column.
+fn tokens(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.split_whitespace().map(move |token| {
+        let offset = token.as_ptr() as usize - s.as_ptr() as usize;
+        (s[..offset].chars().count() + 1, token)
+    })
+}
+
+/// Parses the next token of the line as a number,
+/// along with its column and the token itself.
+fn next_number<'a>(
+    tokens: &mut impl Iterator<Item = (usize, &'a str)>,
+    s: &str,
+    line: usize,
+    expected: &'static str,
+) -> Result<(usize, &'a str, i64), ParseError> {
+    let (column, token) = tokens
+        .next()
+        .ok_or_else(|| ParseError::end_of_line(line, s, expected))?;
+    let number = token
+        .parse()
+        .map_err(|_| ParseError::new(line, column, expected, token))?;
+    Ok((column, token, number))
+}
+
+/// Parses the numbers after `seeds:` on the first line,
+/// along with the column and token of each number.
+fn parse_seed_numbers(first_line: &str) -> Result<Vec<(usize, &str, i64)>, ParseError> {
+    let mut tokens = tokens(first_line).peekable();
+    match tokens.next() {
+        Some((_, "seeds:")) => {}
+        Some((column, token)) => return Err(ParseError::new(1, column, "`seeds:`", token)),
+        None => return Err(ParseError::end_of_line(1, first_line, "`seeds:`")),
+    }
+
+    let mut result = vec![];
+    while tokens.peek().is_some() {
+        result.push(next_number(&mut tokens, first_line, 1, "a seed number")?);
+    }
+    if result.is_empty() {
+        return Err(ParseError::end_of_line(1, first_line, "a seed number"));
+    }
+    Ok(result)
 }
 
 /// Parses a list of numbers corresponding to a list of seeds.
 pub fn parse_seeds_part_one(first_line: &str) -> Result<Vec<i64>, ParseError> {
-    let result = first_line
-        .strip_prefix("seeds: ")
-        .ok_or(ParseError::Format("Invalid first line".into()))?
-        .split_whitespace()
-        .map(|x| x.parse().unwrap())
-        .collect();
-    Ok(result)
+    let result = parse_seed_numbers(first_line)?;
+    Ok(result.into_iter().map(|(_, _, seed)| seed).collect())
 }
 
 /// Parses a list of pairs of numbers corresponding to `pairs` of seeds,
 /// where the first number is the starting seed, and the second is the
 /// length of the range: `start..start + length`.
-pub fn parse_seeds_part_two(first_line: &str) -> Result<Vec<Range<i64>>, ParseError> {
-    let result = first_line
-        .strip_prefix("seeds: ")
-        .ok_or(ParseError::Format("Invalid first line".into()))?
-        .split_whitespace()
-        .map(|x| x.parse().unwrap())
+pub fn parse_seeds_part_two(first_line: &str) -> Result<RangeSet<i64>, ParseError> {
+    let numbers = parse_seed_numbers(first_line)?;
+    if numbers.len() % 2 == 1 {
+        let expected = "the length of the last seed range";
+        return Err(ParseError::end_of_line(1, first_line, expected));
+    }
+
+    numbers
+        .into_iter()
         .tuples()
-        .map(|(start, length)| start..start + length)
-        .collect();
-    Ok(result)
+        .map(|((_, _, start), (column, token, length))| {
+            if length <= 0 {
+                return Err(ParseError::new(1, column, "a positive length", token));
+            }
+            let end = start.checked_add(length).ok_or_else(|| {
+                ParseError::new(1, column, "a length that ends before i64::MAX", token)
+            })?;
+            Ok(start..end)
+        })
+        .collect()
 }
 
-/// Note: The input is expected to not include the first line,
-/// which starts with `seeds:`.
+/// Parses every table in the almanac. Tables are separated by blank lines.
+///
+/// Note: The block of seeds that starts with `seeds:`
+/// is skipped if the input includes it.
 pub fn parse_transition_tables(input: &str) -> Result<Vec<Table>, ParseError> {
-    let re = Regex::new(r"\n\s*\n").map_err(|_| ParseError::Regex)?;
     let mut result = Vec::new();
-    for region in re.split(input) {
-        result.push(region.parse()?);
+    let mut lines = input.lines().enumerate().peekable();
+    while let Some((first, line)) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut region = vec![line];
+        while let Some((_, line)) = lines.next_if(|(_, line)| !line.trim().is_empty()) {
+            region.push(line);
+        }
+        if line.starts_with("seeds:") {
+            continue;
+        }
+        result.push(Table::parse(&region.join("\n"), first + 1)?);
     }
     Ok(result)
 }
 
+/// A problem in a table that parses fine, but makes the
+/// result depend on the order of the lines or overflow.
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    /// The name of the table, such as `seed-to-soil`.
+    pub table: String,
+    /// The line of the range pair, starting from 1.
+    pub line: usize,
+    pub kind: IssueKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum IssueKind {
+    /// The source range overlaps the source range on an earlier line.
+    OverlappingSource(usize),
+    /// The destination range overlaps the destination range on an
+    /// earlier line, so two numbers are sent to the same place.
+    OverlappingDestination(usize),
+    /// The range doesn't contain any numbers.
+    Empty,
+    /// The end of the source or destination range is past `i64::MAX`.
+    Overflow,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} in `{} map`: ", self.line, self.table)?;
+        match self.kind {
+            IssueKind::OverlappingSource(other) => {
+                write!(f, "source range overlaps the one on line {other}")
+            }
+            IssueKind::OverlappingDestination(other) => {
+                write!(f, "destination range overlaps the one on line {other}")
+            }
+            IssueKind::Empty => write!(f, "range has no length"),
+            IssueKind::Overflow => write!(f, "range ends past {}", i64::MAX),
+        }
+    }
+}
+
+/// Checks every table in the almanac for overlapping, empty or
+/// overflowing ranges, which `Table::forward` can't detect on its own.
+///
+/// Lines that can't be parsed are skipped, since the parser reports those.
+pub fn validate(input: &str) -> Vec<Issue> {
+    let mut issues = vec![];
+    // (line, src, dst) of the range pairs in the current table
+    let mut ranges: Vec<(usize, Range<i128>, Range<i128>)> = vec![];
+    let mut table = String::new();
+
+    for (i, line) in input.lines().enumerate() {
+        if let Some(name) = line.trim().strip_suffix(" map:") {
+            table = name.to_string();
+            ranges.clear();
+            continue;
+        }
+
+        let numbers: Vec<i128> = match line
+            .split_whitespace()
+            .map(|x| x.parse::<i64>().map(i128::from))
+            .collect()
+        {
+            Ok(numbers) => numbers,
+            Err(_) => continue,
+        };
+        let [dst, src, len] = numbers[..] else {
+            continue;
+        };
+
+        let line = i + 1;
+        let src = src..src + len;
+        let dst = dst..dst + len;
+        let mut report = |kind| {
+            issues.push(Issue {
+                table: table.clone(),
+                line,
+                kind,
+            })
+        };
+
+        if len <= 0 {
+            report(IssueKind::Empty);
+            continue;
+        }
+        if src.end > i64::MAX as i128 || dst.end > i64::MAX as i128 {
+            report(IssueKind::Overflow);
+        }
+        for (other, other_src, other_dst) in &ranges {
+            if src.start < other_src.end && other_src.start < src.end {
+                report(IssueKind::OverlappingSource(*other));
+            }
+            if dst.start < other_dst.end && other_dst.start < dst.end {
+                report(IssueKind::OverlappingDestination(*other));
+            }
+        }
+        ranges.push((line, src, dst));
+    }
+
+    issues
+}
+
+/// The sets of numbers that the seeds become after each table, starting
+/// with the seeds themselves, along with every piece of a set that is
+/// moved as one, paired with where it ends up in the next set.
+pub struct Trace {
+    pub layers: Vec<RangeSet<i64>>,
+    pub pieces: Vec<Vec<(Range<i64>, Range<i64>)>>,
+}
+
+/// Follows the seeds through each of the tables in turn.
+pub fn trace(tables: &[&Table], seeds: &RangeSet<i64>) -> Trace {
+    let mut layers = vec![seeds.clone()];
+    let mut pieces = vec![];
+    for table in tables {
+        let table = table.normalize();
+        let mut moves = vec![];
+        for range in layers.last().unwrap() {
+            for (piece, offset) in table.segments(range.clone()) {
+                moves.push((piece.clone(), piece.start + offset..piece.end + offset));
+            }
+        }
+        layers.push(moves.iter().map(|(_, dst)| dst.clone()).collect());
+        pieces.push(moves);
+    }
+    Trace { layers, pieces }
+}
+
+/// The smallest range that holds every range in the tables and the trace.
+fn extent(tables: &[&Table], trace: &Trace) -> Range<i64> {
+    let ranges = tables
+        .iter()
+        .flat_map(|table| &table.matches)
+        .flat_map(|range_pair| [&range_pair.src, &range_pair.dst])
+        .chain(trace.layers.iter().flatten());
+    let start = ranges.clone().map(|range| range.start).min().unwrap_or(0);
+    let end = ranges.map(|range| range.end).max().unwrap_or(1);
+    start..end.max(start + 1)
+}
+
+/// Scales a number in the extent to a position in `0.0..=1.0`.
+fn scale(x: i64, extent: &Range<i64>) -> f64 {
+    // In floating point, since the extent can be wider than `i64::MAX`.
+    (x as f64 - extent.start as f64) / (extent.end as f64 - extent.start as f64)
+}
+
+/// Draws each table as a band of two rows, where the source ranges are on
+/// the top row and the destination ranges on the bottom row, and the same
+/// letter marks both ends of a range pair. The rows between the bands show
+/// where the traced seeds are in each category.
+///
+/// For example:
+///     `seed        |        ##            |`
+///     `seed-to-soil|     aaaaaaaaaaaaaaaab|`
+///     `            |      aaaaaaaaaaaaaaaa|`
+///     `            |     b                |`
+///     `soil        |         ##           |`
+pub fn render_ascii(tables: &[&Table], seeds: &RangeSet<i64>, width: usize) -> String {
+    let trace = trace(tables, seeds);
+    let extent = extent(tables, &trace);
+    let columns = |range: &Range<i64>| {
+        // Rounding can put a range that starts near the end past the last column.
+        let start = ((scale(range.start, &extent) * width as f64) as usize).min(width - 1);
+        let end = (scale(range.end, &extent) * width as f64).ceil() as usize;
+        start..end.clamp(start + 1, width)
+    };
+
+    let names: Vec<String> = tables
+        .iter()
+        .map(|table| format!("{}-to-{}", table.source, table.destination))
+        .collect();
+    let label_width = names.iter().map(String::len).max().unwrap_or(0);
+
+    let mut result = String::new();
+    let mut push_row = |label: &str, row: Vec<char>| {
+        let row: String = row.into_iter().collect();
+        writeln!(result, "{label:label_width$}|{row}|").unwrap();
+    };
+
+    let categories = tables
+        .iter()
+        .map(|table| &table.source)
+        .chain(tables.last().map(|table| &table.destination));
+    for (i, category) in categories.enumerate() {
+        let mut row = vec![' '; width];
+        for range in &trace.layers[i] {
+            row[columns(range)].fill('#');
+        }
+        push_row(category, row);
+
+        let Some(table) = tables.get(i) else {
+            break;
+        };
+        let mut src_row = vec![' '; width];
+        let mut dst_row = vec![' '; width];
+        for (j, range_pair) in table.matches.iter().enumerate() {
+            let letter = (b'a' + (j % 26) as u8) as char;
+            src_row[columns(&range_pair.src)].fill(letter);
+            dst_row[columns(&range_pair.dst)].fill(letter);
+        }
+        push_row(&names[i], src_row);
+        push_row("", dst_row);
+    }
+    result
+}
+
+/// Draws each category as a horizontal line, with the range pairs of each
+/// table as bands that connect the source ranges on one line to the
+/// destination ranges on the next. The traced seeds are drawn on top.
+pub fn render_svg(tables: &[&Table], seeds: &RangeSet<i64>) -> String {
+    const WIDTH: f64 = 1000.0;
+    const LEFT: f64 = 160.0;
+    const TOP: f64 = 30.0;
+    const GAP: f64 = 90.0;
+
+    let trace = trace(tables, seeds);
+    let extent = extent(tables, &trace);
+    let x = |n: i64| LEFT + scale(n, &extent) * (WIDTH - LEFT - 20.0);
+    let y = |layer: usize| TOP + layer as f64 * GAP;
+    let band = |src: &Range<i64>, dst: &Range<i64>, layer: usize| {
+        format!(
+            "{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}",
+            x(src.start),
+            y(layer),
+            x(src.end),
+            y(layer),
+            x(dst.end),
+            y(layer + 1),
+            x(dst.start),
+            y(layer + 1),
+        )
+    };
+
+    let height = y(tables.len()) + TOP;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="monospace" font-size="14">"#
+    )
+    .unwrap();
+
+    for (i, table) in tables.iter().enumerate() {
+        for (j, range_pair) in table.matches.iter().enumerate() {
+            let hue = j * 67 % 360;
+            writeln!(
+                svg,
+                r#"  <polygon points="{}" fill="hsl({hue}, 60%, 60%)" fill-opacity="0.35"/>"#,
+                band(&range_pair.src, &range_pair.dst, i)
+            )
+            .unwrap();
+        }
+    }
+
+    let categories = tables
+        .iter()
+        .map(|table| &table.source)
+        .chain(tables.last().map(|table| &table.destination));
+    for (i, category) in categories.enumerate() {
+        writeln!(
+            svg,
+            r#"  <line x1="{LEFT}" y1="{0}" x2="{1}" y2="{0}" stroke="gray"/>"#,
+            y(i),
+            WIDTH - 20.0
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"  <text x="10" y="{:.1}">{category}</text>"#,
+            y(i) + 5.0
+        )
+        .unwrap();
+    }
+
+    for (i, pieces) in trace.pieces.iter().enumerate() {
+        for (src, dst) in pieces {
+            writeln!(
+                svg,
+                r#"  <polygon points="{}" fill="crimson" fill-opacity="0.8"/>"#,
+                band(src, dst, i)
+            )
+            .unwrap();
+        }
+    }
+    for (i, layer) in trace.layers.iter().enumerate() {
+        for range in layer {
+            writeln!(
+                svg,
+                r#"  <rect x="{:.1}" y="{:.1}" width="{:.1}" height="6" fill="crimson"/>"#,
+                x(range.start),
+                y(i) - 3.0,
+                (x(range.end) - x(range.start)).max(1.0)
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{part_one, part_two, Table};
+    use super::{
+        collapse, parse_transition_tables, part_one, part_two, render_ascii, render_svg, trace,
+        validate, Almanac, AlmanacError, Issue, IssueKind, ParseError, RangeSet, Table,
+    };
 
     const INPUT: &str = concat!(
         "seeds: 79 14 55 13\n",
@@ -228,12 +1001,12 @@ mod tests {
 
     #[test]
     fn test_part_one() {
-        assert_eq!(35, part_one(INPUT));
+        assert_eq!(Ok(35), part_one(INPUT));
     }
 
     #[test]
     fn test_part_two() {
-        assert_eq!(46, part_two(INPUT));
+        assert_eq!(Ok(46), part_two(INPUT));
     }
 
     #[test]
@@ -245,4 +1018,338 @@ mod tests {
         assert_eq!(50, table.matches[0].dst.start);
         assert_eq!(98, table.matches[0].src.start);
     }
+
+    #[test]
+    fn test_forward_ranges() {
+        let input = concat!("seed-to-soil map:\n", "50 98 2\n", "52 50 48\n",);
+        let table = input.parse::<Table>().expect("failed to parse table");
+
+        let ranges = table.forward_ranges(&[40..60, 90..110].into_iter().collect());
+        assert_eq!(
+            ranges.iter().cloned().collect::<Vec<_>>(),
+            vec![40..62, 92..110]
+        );
+
+        for x in (40..60).chain(90..110) {
+            let y = table.forward(x);
+            assert!(ranges.contains(&y), "{x} => {y}");
+        }
+    }
+
+    #[test]
+    fn test_near_max() {
+        // Both ranges end before i64::MAX, but `dst.start + num` would not.
+        let input = concat!(
+            "seeds: 9223372036854775000 10\n\n",
+            "seed-to-location map:\n",
+            "9223372036854775100 9223372036854775000 100\n",
+        );
+        assert!(validate(input).is_empty());
+        // The seed 10 is outside the map, but the big one must still be translated.
+        assert_eq!(Ok(10), part_one(input));
+        assert_eq!(Ok(9223372036854775100), part_two(input));
+    }
+
+    #[test]
+    fn test_part_two_no_matches() {
+        let input = "seeds: 1000 10\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(Ok(1000), part_two(input));
+    }
+
+    #[test]
+    fn test_compose() {
+        let tables = parse_transition_tables(INPUT).expect("failed to parse transition tables");
+        let table = collapse(&tables.iter().collect::<Vec<_>>());
+
+        assert!(table
+            .matches
+            .windows(2)
+            .all(|pair| pair[0].src.end <= pair[1].src.start));
+        for seed in -10..200 {
+            let expected = tables.iter().fold(seed, |acc, table| table.forward(acc));
+            assert_eq!(expected, table.forward(seed), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_compose_overlapping() {
+        let a = "a-to-b map:\n10 0 5\n20 3 5\n".parse::<Table>().unwrap();
+        let b = "b-to-c map:\n100 12 4\n0 5 1\n".parse::<Table>().unwrap();
+        let table = a.compose(&b);
+        for x in -5..20 {
+            assert_eq!(b.forward(a.forward(x)), table.forward(x), "x = {x}");
+        }
+    }
+
+    #[test]
+    fn test_invert() {
+        let tables = parse_transition_tables(INPUT).expect("failed to parse transition tables");
+        let table = collapse(&tables.iter().collect::<Vec<_>>());
+        let inverse = table
+            .invert()
+            .expect("expected the example to be invertible");
+        for seed in -10..200 {
+            assert_eq!(seed, inverse.forward(table.forward(seed)), "seed {seed}");
+        }
+        assert_eq!(Some(table.clone()), inverse.invert());
+    }
+
+    #[test]
+    fn test_invert_not_one_to_one() {
+        // 0..5 is sent to 10..15, but 10..15 still maps to itself.
+        let table = "a-to-b map:\n10 0 5\n".parse::<Table>().unwrap();
+        assert_eq!(None, table.invert());
+
+        // Both ranges are sent to 0..5.
+        let table = "a-to-b map:\n0 5 5\n0 10 5\n".parse::<Table>().unwrap();
+        assert_eq!(None, table.invert());
+
+        let table = "a-to-b map:\n5 0 5\n0 5 5\n".parse::<Table>().unwrap();
+        assert!(table.invert().is_some());
+    }
+
+    #[test]
+    fn test_display() {
+        let table = "a-to-b map:\n52 50 48\n50 98 2\n".parse::<Table>().unwrap();
+        assert_eq!(
+            "a-to-b map:\n[50..98] => [52..100]\n[98..100] => [50..52]\n_ => x",
+            table.normalize().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let table = "seed-to-soil map:\n50 98 2\n".parse::<Table>().unwrap();
+        assert_eq!("seed", table.source);
+        assert_eq!("soil", table.destination);
+        assert!("seed-soil map:\n50 98 2\n".parse::<Table>().is_err());
+    }
+
+    #[test]
+    fn test_conversion() {
+        let almanac = INPUT.parse::<Almanac>().expect("failed to parse almanac");
+        let tables = parse_transition_tables(INPUT).unwrap();
+
+        let table = almanac.conversion("soil", "humidity").unwrap();
+        assert_eq!(("soil", "humidity"), (&*table.source, &*table.destination));
+        for soil in 0..120 {
+            let expected = tables[1..6]
+                .iter()
+                .fold(soil, |acc, table| table.forward(acc));
+            assert_eq!(expected, table.forward(soil), "soil {soil}");
+        }
+
+        let forward = almanac.conversion("seed", "location").unwrap();
+        let backward = almanac.conversion("location", "seed").unwrap();
+        assert_eq!(
+            ("location", "seed"),
+            (&*backward.source, &*backward.destination)
+        );
+        for seed in 0..120 {
+            assert_eq!(seed, backward.forward(forward.forward(seed)));
+        }
+
+        assert_eq!(
+            Table::default().matches,
+            almanac.conversion("water", "water").unwrap().matches
+        );
+    }
+
+    #[test]
+    fn test_conversion_in_any_order() {
+        let mut regions: Vec<&str> = INPUT.split("\n\n").skip(1).collect();
+        regions.reverse();
+        let almanac = regions.join("\n\n").parse::<Almanac>().unwrap();
+        let table = almanac.conversion("seed", "location").unwrap();
+        assert_eq!(82, table.forward(79));
+    }
+
+    #[test]
+    fn test_almanac_errors() {
+        let almanac = INPUT.parse::<Almanac>().unwrap();
+        assert_eq!(
+            Err(AlmanacError::MissingLink("seed".into(), "moon".into())),
+            almanac.conversion("seed", "moon")
+        );
+
+        let input = "a-to-b map:\n0 10 5\n\na-to-c map:\n0 10 5\n";
+        assert_eq!(
+            Err(AlmanacError::Duplicate("a".into())),
+            input.parse::<Almanac>().map(|_| ())
+        );
+
+        let input = "a-to-b map:\n0 10 5\n\nb-to-c map:\n0 10 5\n\nc-to-a map:\n1 2 3\n";
+        assert_eq!(
+            Err(AlmanacError::Cycle(vec![
+                "a".into(),
+                "b".into(),
+                "c".into(),
+                "a".into()
+            ])),
+            input.parse::<Almanac>().map(|_| ())
+        );
+
+        let input = "a-to-b map:\n10 0 5\n";
+        let almanac = input.parse::<Almanac>().unwrap();
+        assert_eq!(
+            Err(AlmanacError::NotInvertible("b".into(), "a".into())),
+            almanac.conversion("b", "a")
+        );
+    }
+
+    #[test]
+    fn test_validate_example() {
+        assert_eq!(Vec::<Issue>::new(), validate(INPUT));
+    }
+
+    #[test]
+    fn test_validate() {
+        let input = concat!(
+            "seeds: 1 2\n",
+            "\n",
+            "seed-to-soil map:\n",
+            "50 98 2\n",
+            "52 90 10\n",
+            "51 0 5\n",
+            "\n",
+            "soil-to-fertilizer map:\n",
+            "0 15 0\n",
+            "9223372036854775800 0 100\n",
+        );
+        let issue = |table: &str, line, kind| Issue {
+            table: table.into(),
+            line,
+            kind,
+        };
+        assert_eq!(
+            vec![
+                issue("seed-to-soil", 5, IssueKind::OverlappingSource(4)),
+                issue("seed-to-soil", 6, IssueKind::OverlappingDestination(4)),
+                issue("seed-to-soil", 6, IssueKind::OverlappingDestination(5)),
+                issue("soil-to-fertilizer", 9, IssueKind::Empty),
+                issue("soil-to-fertilizer", 10, IssueKind::Overflow),
+            ],
+            validate(input)
+        );
+        assert_eq!(
+            "line 5 in `seed-to-soil map`: source range overlaps the one on line 4",
+            validate(input)[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |line, column, expected, found: Option<&str>| {
+            Err(AlmanacError::Parse(ParseError {
+                line,
+                column,
+                expected,
+                found: found.map(String::from),
+            }))
+        };
+
+        let input = "seeds: 1 2\n\nseed-to-soil map:\n50 98 2\n52 5x 48\n";
+        assert_eq!(error(5, 4, "the `src` field", Some("5x")), part_one(input));
+
+        let input = "seeds: 1 2\n\n\nseed-to-soil map:\n50 98\n";
+        assert_eq!(error(5, 6, "the `len` field", None), part_one(input));
+
+        let input = "seeds: 1 2\n\nseed-to-soil map:\n50 98 2 7\n";
+        assert_eq!(
+            error(4, 9, "the end of the line", Some("7")),
+            part_one(input)
+        );
+
+        let input = "seeds: 1 2\n\nseed-to-soil map:\n9223372036854775807 1 2\n";
+        let expected = "a `len` that ends before i64::MAX";
+        assert_eq!(error(4, 23, expected, Some("2")), part_one(input));
+
+        let input = "seeds: 1 2\n\nseed to soil:\n50 98 2\n";
+        let expected = "a header of the form `X-to-Y map:`";
+        assert_eq!(error(3, 1, expected, Some("seed")), part_one(input));
+
+        let input = "seed: 1 2\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(error(1, 1, "`seeds:`", Some("seed:")), part_one(input));
+
+        let input = "seeds: 1 two\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(error(1, 10, "a seed number", Some("two")), part_one(input));
+
+        let input = "seeds:\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(error(1, 7, "a seed number", None), part_one(input));
+
+        let input = "seeds: 1 2 3\n\nseed-to-location map:\n50 98 2\n";
+        let expected = "the length of the last seed range";
+        assert_eq!(error(1, 13, expected, None), part_two(input));
+
+        let input = "seeds: 1 0\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(
+            error(1, 10, "a positive length", Some("0")),
+            part_two(input)
+        );
+
+        let input = "seeds: 1 -007\n\nseed-to-location map:\n50 98 2\n";
+        assert_eq!(
+            error(1, 10, "a positive length", Some("-007")),
+            part_two(input)
+        );
+    }
+
+    #[test]
+    fn test_parse_error_display() {
+        let input = "seeds: 1 2\n\nseed-to-soil map:\n50 98 2\n52 5x 48\n";
+        let err = part_one(input).unwrap_err();
+        assert_eq!(
+            "line 5, column 4: expected the `src` field, found `5x`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_trace() {
+        let almanac = INPUT.parse::<Almanac>().unwrap();
+        let tables = almanac.path("seed", "location").unwrap();
+        let trace = trace(&tables, &RangeSet::from(79..80));
+        let layers: Vec<i64> = trace
+            .layers
+            .iter()
+            .map(|layer| layer.first().unwrap())
+            .collect();
+        assert_eq!(vec![79, 81, 81, 81, 74, 78, 78, 82], layers);
+
+        let trace = super::trace(&tables, &RangeSet::from(79..93));
+        let expected: RangeSet<i64> = (79..93)
+            .map(|seed| tables.iter().fold(seed, |acc, table| table.forward(acc)))
+            .map(|location| location..location + 1)
+            .collect();
+        assert_eq!(expected, trace.layers[7]);
+    }
+
+    #[test]
+    fn test_render() {
+        let almanac = INPUT.parse::<Almanac>().unwrap();
+        let tables = almanac.path("seed", "location").unwrap();
+        let seeds = RangeSet::from(79..80);
+
+        let ascii = render_ascii(&tables, &seeds, 40);
+        let lines: Vec<&str> = ascii.lines().collect();
+        assert_eq!(8 + 7 * 2, lines.len());
+        assert!(lines[0].starts_with("seed                   |"));
+        assert!(lines[1].starts_with("seed-to-soil           |"));
+        assert!(lines
+            .iter()
+            .all(|line| line.chars().count() == 23 + 1 + 40 + 1));
+        assert_eq!(1, lines[0].matches('#').count());
+
+        let svg = render_svg(&tables, &seeds);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert_eq!(8, svg.matches("<line").count());
+        assert_eq!(8, svg.matches("<rect").count());
+
+        // A range right at the top of the extent still gets a column.
+        let seeds = RangeSet::from(i64::MAX - 10..i64::MAX);
+        let ascii = render_ascii(&tables, &seeds, 40);
+        assert!(ascii.lines().next().unwrap().ends_with("#|"));
+        render_svg(&tables, &seeds);
+    }
 }
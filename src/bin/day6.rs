@@ -1,107 +1,531 @@
-use anyhow::{anyhow, Context, Error, Result};
+use anyhow::{anyhow, Context, Result};
+use num::integer::Roots;
+use num::{BigUint, Integer};
+use std::env;
+use std::fmt;
 use std::iter;
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::fs;
 
-fn main() {
+fn main() -> Result<()> {
     let input = fs::read_to_string("inputs/6.txt").expect("Failed to read input file");
-    println!("Part one: {}", part_one(&input));
-    println!("Part two: {}", part_two(&input));
+
+    // `--model linear|quadratic|cap:SPEED|drag:K` prints an analysis of every race.
+    let args: Vec<String> = env::args().skip(1).collect();
+    match &args[..] {
+        [] => {}
+        [flag, model] if flag == "--model" => {
+            let model = parse_model(model)?;
+            let races = RaceSheet::<i64>::parse(&input, Kerning::Separate)?;
+            println!("{}", RaceAnalysis::HEADER);
+            for race in analyze(model.as_ref(), &races.times, &races.distances) {
+                println!("{race}");
+            }
+            return Ok(());
+        }
+        _ => {
+            return Err(anyhow!(
+                "usage: day6 [--model linear|quadratic|cap:SPEED|drag:K]"
+            ))
+        }
+    }
+
+    println!("Part one: {}", part_one(&input)?);
+    println!("Part two: {}", part_two(&input)?);
+    Ok(())
 }
 
-pub fn part_one(input: &str) -> i64 {
-    let input = input.parse::<PartOne>().expect("failed to parse input");
+pub fn part_one(input: &str) -> Result<BigUint> {
+    let input = RaceSheet::<i64>::parse(input, Kerning::Separate)?;
 
-    let mut result = 1;
+    let mut result = BigUint::from(1u32);
     for (t, d) in iter::zip(input.times, input.distances) {
-        result *= calculate_ways_to_win(t, d);
+        // Widened so that `time * time` can't overflow, and the product
+        // of the ways to win is unbounded with enough races.
+        let ways = calculate_ways_to_win(t as i128, d as i128);
+        result *= ways.unsigned_abs();
     }
-    result
+    Ok(result)
 }
 
-pub fn part_two(input: &str) -> i64 {
-    let input = input.parse::<PartTwo>().expect("failed to parse input");
-    calculate_ways_to_win(input.time, input.distance)
+pub fn part_two(input: &str) -> Result<BigUint> {
+    let input = RaceSheet::<BigUint>::parse(input, Kerning::Joined)?;
+    let [time, distance] = [input.times, input.distances].map(|mut v| v.remove(0));
+    Ok(calculate_ways_to_win(time, distance))
 }
 
 /// The number of ways to achieve a distance greater than `record`.
-pub fn calculate_ways_to_win(time: i64, record: i64) -> i64 {
-    let mut ways = 0;
-    for hold_time in 1..time {
-        let travel_time = time - hold_time;
-        let distance = hold_time * travel_time;
-        if distance > record {
-            ways += 1;
+///
+/// Holding the button for `h` wins when `h * (time - h) > record`. The
+/// distance is symmetric around `time / 2`, so the winning hold times are
+/// `low..=time - low`, where `low` is just above the smaller root of the
+/// quadratic `(time - sqrt(time^2 - 4 * record)) / 2`.
+///
+/// The integer square root is at most one below the real one, so the root
+/// is only a starting point, and `low` is found exactly by checking the
+/// distance of the next couple of hold times. This works for any integer
+/// type, including `BigUint` for arbitrarily long inputs.
+pub fn calculate_ways_to_win<T: Integer + Roots + Clone>(time: T, record: T) -> T {
+    let two = T::one() + T::one();
+    let wins = |hold_time: &T| hold_time.clone() * (time.clone() - hold_time.clone()) > record;
+
+    let square = time.clone() * time.clone();
+    let four_records = two.clone() * two.clone() * record.clone();
+    if square <= four_records {
+        return T::zero();
+    }
+
+    // The button has to be held for at least 1 millisecond.
+    let root = (square - four_records).sqrt();
+    let mut low = ((time.clone() - root) / two.clone()).max(T::one());
+    let half = time.clone() / two.clone();
+    while low <= half && !wins(&low) {
+        low = low + T::one();
+    }
+    if low > half {
+        return T::zero();
+    }
+    time - two * low + T::one()
+}
+
+/// How far a boat travels in a race, which lets the puzzle be
+/// played with different rules for how the boat charges and moves.
+pub trait BoatModel {
+    /// The distance travelled when the button is held for `hold_time`
+    /// milliseconds of a race that lasts `time` milliseconds.
+    ///
+    /// For a fixed `time`, the distance is expected to increase up to a
+    /// single best hold time and then decrease, which is what lets
+    /// `winning_hold_times` use a search instead of trying every hold time.
+    fn distance(&self, hold_time: i64, time: i64) -> f64;
+}
+
+/// The puzzle's rules: each millisecond of holding adds 1 mm/ms of speed.
+pub struct Linear;
+
+impl BoatModel for Linear {
+    fn distance(&self, hold_time: i64, time: i64) -> f64 {
+        hold_time as f64 * (time - hold_time) as f64
+    }
+}
+
+/// The speed grows with the square of the hold time.
+pub struct QuadraticCharge;
+
+impl BoatModel for QuadraticCharge {
+    fn distance(&self, hold_time: i64, time: i64) -> f64 {
+        let speed = hold_time as f64 * hold_time as f64;
+        speed * (time - hold_time) as f64
+    }
+}
+
+/// Like `Linear`, but the boat can't go faster than `max_speed`.
+pub struct SpeedCap {
+    pub max_speed: i64,
+}
+
+impl BoatModel for SpeedCap {
+    fn distance(&self, hold_time: i64, time: i64) -> f64 {
+        hold_time.min(self.max_speed) as f64 * (time - hold_time) as f64
+    }
+}
+
+/// Like `Linear`, but once released, the boat loses speed to drag,
+/// so its speed after `t` milliseconds is `hold_time * e^(-k * t)`.
+pub struct Drag {
+    pub k: f64,
+}
+
+impl BoatModel for Drag {
+    fn distance(&self, hold_time: i64, time: i64) -> f64 {
+        let travel_time = (time - hold_time) as f64;
+        if self.k == 0.0 {
+            return hold_time as f64 * travel_time;
         }
+        hold_time as f64 * (1.0 - (-self.k * travel_time).exp()) / self.k
     }
-    ways
 }
 
-struct PartOne {
-    times: Vec<i64>,
-    distances: Vec<i64>,
+/// Parses a model such as `linear`, `quadratic`, `cap:40` or `drag:0.01`.
+pub fn parse_model(s: &str) -> Result<Box<dyn BoatModel>> {
+    let (name, arg) = s.split_once(':').unwrap_or((s, ""));
+    match name {
+        "linear" => Ok(Box::new(Linear)),
+        "quadratic" => Ok(Box::new(QuadraticCharge)),
+        "cap" => Ok(Box::new(SpeedCap {
+            max_speed: arg.parse().context("failed to parse max speed")?,
+        })),
+        "drag" => Ok(Box::new(Drag {
+            k: arg.parse().context("failed to parse drag coefficient")?,
+        })),
+        _ => Err(anyhow!("unknown boat model `{name}`")),
+    }
 }
 
-impl FromStr for PartOne {
-    type Err = Error;
+/// The hold time that travels the farthest, preferring the shortest one.
+pub fn best_hold_time(model: &(impl BoatModel + ?Sized), time: i64) -> i64 {
+    let distance = |hold_time| model.distance(hold_time, time);
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<_> = s.lines().collect();
-        if lines.len() != 2 {
-            return Err(anyhow!("input must have exactly 2 lines"));
+    // A ternary search narrows down the peak, and the last few
+    // candidates are checked one by one.
+    let (mut lo, mut hi) = (0, time.max(0));
+    while hi - lo > 2 {
+        let m1 = lo + (hi - lo) / 3;
+        let m2 = hi - (hi - lo) / 3;
+        match distance(m1).total_cmp(&distance(m2)) {
+            std::cmp::Ordering::Less => lo = m1 + 1,
+            std::cmp::Ordering::Greater => hi = m2 - 1,
+            std::cmp::Ordering::Equal => (lo, hi) = (m1, m2),
         }
-        let times = parse_numbers(
-            lines[0]
-                .strip_prefix("Time:")
-                .context("expected first line to start with `Time`")?,
-        )?;
-        let distances = parse_numbers(
-            lines[1]
-                .strip_prefix("Distance:")
-                .context("expected first line to start with `Distance`")?,
-        )?;
-        Ok(Self { times, distances })
-    }
-}
-
-/// Splits a string by whitespace and maps each item to a number.
-pub fn parse_numbers(s: &str) -> Result<Vec<i64>> {
-    s.split_whitespace()
-        .map(str::parse::<i64>)
-        .collect::<Result<_, _>>()
-        .map_err(Error::new)
-}
-
-/// The input data for part two.
-struct PartTwo {
+    }
+    (lo..=hi)
+        .rev()
+        .max_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+        .unwrap()
+}
+
+/// The hold times that travel farther than `record`, if there are any.
+///
+/// Since the distance only increases up to the best hold time and only
+/// decreases after it, both ends of the interval are found by a binary
+/// search on either side of the best hold time.
+pub fn winning_hold_times(
+    model: &(impl BoatModel + ?Sized),
     time: i64,
-    distance: i64,
+    record: f64,
+) -> Option<RangeInclusive<i64>> {
+    let wins = |hold_time| model.distance(hold_time, time) > record;
+    let best = best_hold_time(model, time);
+    if !wins(best) {
+        return None;
+    }
+
+    // The first hold time in `lo..=best` that wins.
+    let (mut lo, mut hi) = (0, best);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if wins(mid) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    let low = lo;
+
+    // The last hold time in `best..=time` that wins.
+    let (mut lo, mut hi) = (best, time);
+    while lo < hi {
+        let mid = lo + (hi - lo + 1) / 2;
+        if wins(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Some(low..=lo)
 }
 
-impl FromStr for PartTwo {
-    type Err = Error;
+/// A summary of one race under a particular boat model.
+#[derive(Debug, PartialEq)]
+pub struct RaceAnalysis {
+    pub time: i64,
+    pub record: i64,
+    /// The hold time that travels the farthest.
+    pub best_hold_time: i64,
+    pub best_distance: f64,
+    pub winning: Option<RangeInclusive<i64>>,
+    /// The number of winning hold times.
+    pub margin: i64,
+    /// How much farther than the record the best hold time travels.
+    pub slack: f64,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lines: Vec<_> = s.lines().collect();
-        if lines.len() != 2 {
-            return Err(anyhow!("input must have exactly 2 lines"));
+impl RaceAnalysis {
+    pub const HEADER: &'static str =
+        "  time   record  best hold  best distance       winning  margin     slack";
+}
+
+impl fmt::Display for RaceAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let winning = match &self.winning {
+            Some(range) => format!("{}..={}", range.start(), range.end()),
+            None => "-".to_string(),
+        };
+        write!(
+            f,
+            "{:>6} {:>8} {:>10} {:>14.1} {:>13} {:>7} {:>9.1}",
+            self.time,
+            self.record,
+            self.best_hold_time,
+            self.best_distance,
+            winning,
+            self.margin,
+            self.slack
+        )
+    }
+}
+
+/// Analyzes each race, given as pairs of times and records.
+pub fn analyze(
+    model: &(impl BoatModel + ?Sized),
+    times: &[i64],
+    records: &[i64],
+) -> Vec<RaceAnalysis> {
+    iter::zip(times, records)
+        .map(|(&time, &record)| {
+            let best_hold_time = best_hold_time(model, time);
+            let best_distance = model.distance(best_hold_time, time);
+            let winning = winning_hold_times(model, time, record as f64);
+            let margin = winning
+                .as_ref()
+                .map_or(0, |range| range.end() - range.start() + 1);
+            RaceAnalysis {
+                time,
+                record,
+                best_hold_time,
+                best_distance,
+                winning,
+                margin,
+                slack: best_distance - record as f64,
+            }
+        })
+        .collect()
+}
+
+/// How the columns of the race sheet are read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kerning {
+    /// Every column is a separate race.
+    Separate,
+    /// The spaces between the columns are bad kerning,
+    /// so the digits of each line form a single race.
+    Joined,
+}
+
+/// The times and record distances of the races on the sheet.
+#[derive(Debug, PartialEq)]
+pub struct RaceSheet<T> {
+    pub times: Vec<T>,
+    pub distances: Vec<T>,
+}
+
+impl<T> RaceSheet<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    /// Parses a `Time:` line and a `Distance:` line, ignoring blank lines
+    /// and surrounding whitespace, and reading the columns by `kerning`.
+    pub fn parse(s: &str, kerning: Kerning) -> Result<Self> {
+        let lines: Vec<&str> = s.lines().filter(|line| !line.trim().is_empty()).collect();
+        let [time_line, distance_line] = lines[..] else {
+            return Err(anyhow!(
+                "expected a `Time` line and a `Distance` line, found {} lines",
+                lines.len()
+            ));
+        };
+
+        let time_columns = columns(time_line, "Time:")?;
+        let distance_columns = columns(distance_line, "Distance:")?;
+        if time_columns.len() != distance_columns.len() {
+            return Err(anyhow!(
+                "found {} times, but {} distances",
+                time_columns.len(),
+                distance_columns.len()
+            ));
         }
-        let time = lines[0]
-            .strip_prefix("Time:")
-            .context("expected first line to start with `Time`")?
-            .chars()
-            .filter(char::is_ascii_digit)
-            .collect::<String>()
-            .parse()
-            .context("failed to parse numbers")?;
-        let distance = lines[1]
-            .strip_prefix("Distance:")
-            .context("expected first line to start with `Distance`")?
-            .chars()
-            .filter(char::is_ascii_digit)
-            .collect::<String>()
-            .parse()
-            .context("failed to parse numbers")?;
-        Ok(Self { time, distance })
+        if time_columns.is_empty() {
+            return Err(anyhow!("expected at least one race"));
+        }
+
+        let parse = |columns: Vec<&str>, label: &str| -> Result<Vec<T>> {
+            let columns = match kerning {
+                Kerning::Separate => columns.into_iter().map(str::to_owned).collect(),
+                Kerning::Joined => vec![columns.concat()],
+            };
+            columns
+                .into_iter()
+                .map(|column| {
+                    column
+                        .parse()
+                        .with_context(|| format!("failed to parse {label} `{column}`"))
+                })
+                .collect()
+        };
+        Ok(Self {
+            times: parse(time_columns, "time")?,
+            distances: parse(distance_columns, "distance")?,
+        })
+    }
+}
+
+/// Splits a line that starts with `label` into its columns.
+fn columns<'a>(line: &'a str, label: &str) -> Result<Vec<&'a str>> {
+    let rest = line
+        .trim()
+        .strip_prefix(label)
+        .with_context(|| format!("expected line to start with `{label}`: `{line}`"))?;
+    Ok(rest.split_whitespace().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        analyze, calculate_ways_to_win, parse_model, part_one, part_two, winning_hold_times,
+        BoatModel, Drag, Kerning, Linear, QuadraticCharge, RaceSheet, SpeedCap,
+    };
+    use num::BigUint;
+
+    const INPUT: &str = concat!("Time:      7  15   30\n", "Distance:  9  40  200\n",);
+
+    /// The number of ways to win, by trying every hold time.
+    fn brute_force(time: i64, record: i64) -> i64 {
+        (1..time)
+            .filter(|hold_time| hold_time * (time - hold_time) > record)
+            .count() as i64
+    }
+
+    #[test]
+    fn test_example() {
+        assert_eq!(BigUint::from(288u32), part_one(INPUT).unwrap());
+        assert_eq!(BigUint::from(71503u32), part_two(INPUT).unwrap());
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        for time in 0..120 {
+            for record in -3..time * time / 4 + 3 {
+                let expected = brute_force(time, record);
+                assert_eq!(
+                    expected,
+                    calculate_ways_to_win(time, record),
+                    "{time} {record}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_values() {
+        // `time * time` overflows `i64`, but not the widened type in `part_one`.
+        let time = i64::MAX as i128;
+        assert_eq!(time - 1, calculate_ways_to_win(time, 0));
+
+        // The product of the ways to win for several races overflows as well.
+        let input = format!("Time: {} {}\nDistance: 0 0\n", i64::MAX, i64::MAX);
+        let ways = BigUint::from(i64::MAX as u64 - 1);
+        assert_eq!(&ways * &ways, part_one(&input).unwrap());
+
+        // Near the boundary the answer is exact, where `f64` would round.
+        let time: BigUint = "1".repeat(60).parse().unwrap();
+        let low: BigUint = "123456789".repeat(5).parse().unwrap();
+        let record = &low * (&time - &low) - 1u32;
+        let ways = calculate_ways_to_win(time.clone(), record.clone());
+        assert_eq!(&time - 2u32 * &low + 1u32, ways);
+        let ways = calculate_ways_to_win(time.clone(), record + 1u32);
+        assert_eq!(&time - 2u32 * &low - 1u32, ways);
+    }
+
+    #[test]
+    fn test_linear_matches_closed_form() {
+        for time in 0..80 {
+            for record in 0..time * time / 4 + 2 {
+                let ways = winning_hold_times(&Linear, time, record as f64)
+                    .map_or(0, |range| range.count() as i64);
+                // Holding for the whole race travels 0, which never wins here.
+                assert_eq!(calculate_ways_to_win(time, record), ways, "{time} {record}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_models_against_brute_force() {
+        let models: Vec<Box<dyn BoatModel>> = vec![
+            Box::new(Linear),
+            Box::new(QuadraticCharge),
+            Box::new(SpeedCap { max_speed: 7 }),
+            Box::new(Drag { k: 0.05 }),
+            Box::new(Drag { k: 0.0 }),
+        ];
+        for model in &models {
+            for time in 0..60 {
+                for record in [0.0, 10.0, 55.5, 200.0, 1000.0] {
+                    let winners: Vec<i64> = (0..=time)
+                        .filter(|&hold_time| model.distance(hold_time, time) > record)
+                        .collect();
+                    let expected = winners
+                        .first()
+                        .map(|&first| first..=*winners.last().unwrap());
+                    assert_eq!(
+                        expected,
+                        winning_hold_times(model.as_ref(), time, record),
+                        "{time} {record}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let model = parse_model("cap:10").unwrap();
+        let races = analyze(model.as_ref(), &[7, 30], &[9, 200]);
+        assert_eq!(3, races[0].best_hold_time);
+        assert_eq!(Some(2..=5), races[0].winning);
+        assert_eq!(4, races[0].margin);
+        assert_eq!(3.0, races[0].slack);
+
+        // Without the cap the best hold time would be 15.
+        assert_eq!(10, races[1].best_hold_time);
+        // It only ties the record, so there is no way to win.
+        assert_eq!(None, races[1].winning);
+        assert_eq!(0, races[1].margin);
+        assert_eq!(0.0, races[1].slack);
+
+        assert!(parse_model("warp").is_err());
+        assert!(parse_model("drag:x").is_err());
+    }
+
+    #[test]
+    fn test_race_sheet() {
+        let sheet = RaceSheet::<i64>::parse(INPUT, Kerning::Separate).unwrap();
+        assert_eq!(vec![7, 15, 30], sheet.times);
+        assert_eq!(vec![9, 40, 200], sheet.distances);
+
+        let sheet = RaceSheet::<i64>::parse(INPUT, Kerning::Joined).unwrap();
+        assert_eq!(vec![71530], sheet.times);
+        assert_eq!(vec![940200], sheet.distances);
+
+        let input = "\r\nTime:   7  15 \r\nDistance:  9  40  \r\n\r\n";
+        let sheet = RaceSheet::<i64>::parse(input, Kerning::Separate).unwrap();
+        assert_eq!(vec![7, 15], sheet.times);
+        assert_eq!(vec![9, 40], sheet.distances);
+    }
+
+    #[test]
+    fn test_race_sheet_errors() {
+        let error = |input: &str| {
+            RaceSheet::<i64>::parse(input, Kerning::Separate)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            "found 3 times, but 2 distances",
+            error("Time: 7 15 30\nDistance: 9 40\n")
+        );
+        assert_eq!(
+            "expected line to start with `Distance:`: `Distanc: 9`",
+            error("Time: 7\nDistanc: 9\n")
+        );
+        assert_eq!(
+            "expected a `Time` line and a `Distance` line, found 1 lines",
+            error("Time: 7\n")
+        );
+        assert_eq!("expected at least one race", error("Time:\nDistance:\n"));
+        assert_eq!(
+            "failed to parse distance `9x`",
+            error("Time: 7\nDistance: 9x\n")
+        );
     }
 }
@@ -1,176 +1,927 @@
 use std::fs;
-use std::cmp::{Ord, Ordering};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
 
-fn main() {
-    let input = fs::read_to_string("inputs/7.txt").expect("failed to read input file");
-    println!("Part one: {}", run(&input, false));
-    println!("Part two: {}", run(&input, true));
+use anyhow::{anyhow, Context, Result};
+
+fn main() -> Result<()> {
+    let input = || fs::read_to_string("inputs/7.txt").expect("failed to read input file");
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match &args[..] {
+        [] => {
+            let input = input();
+            println!("Part one: {}", run(&input, &Rules::part_one())?);
+            println!("Part two: {}", run(&input, &Rules::part_two())?);
+        }
+        [flag] if flag == "--explain" => {
+            let input = input();
+            println!("Part one:\n{}", explain(&input, &Rules::part_one())?);
+            println!("Part two:\n{}", explain(&input, &Rules::part_two())?);
+        }
+        [flag, path] if flag == "--rules" => {
+            let spec =
+                fs::read_to_string(path).with_context(|| format!("failed to read `{path}`"))?;
+            let rules: Rules = spec
+                .parse()
+                .with_context(|| format!("invalid rules in `{path}`"))?;
+            println!("House rules: {}", run(&input(), &rules)?);
+        }
+        [flag, path] if flag == "--poker" => {
+            let hands =
+                fs::read_to_string(path).with_context(|| format!("failed to read `{path}`"))?;
+            println!("Poker: {}", run_poker(&hands)?);
+        }
+        [flag, cards] if flag == "--equity" => {
+            for (name, rules) in [
+                ("part one", Rules::part_one()),
+                ("part two", Rules::part_two()),
+            ] {
+                let hand: Hand<5> = Hand::new(parse_cards(cards, &rules)?, 0, &rules)?;
+                let total = hands(&rules);
+                println!("Under {name} rules:");
+                for (rank, count) in distribution::<5>(&rules) {
+                    let share = 100.0 * count as f64 / total as f64;
+                    println!("  {:<14} {count:>7} {share:>6.2}%", format!("{rank:?}"));
+                }
+                let equity = equity(&hand, &rules);
+                println!(
+                    "  {cards} is a {:?} at the {:.2}% percentile, and a random hand beats it with probability {:.4}",
+                    hand.rank,
+                    equity.percentile(),
+                    equity.beaten()
+                );
+            }
+        }
+        _ => {
+            return Err(anyhow!(
+                "usage: day7 [--explain | --rules FILE | --poker FILE | --equity CARDS]"
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// The largest hand size that `run` supports.
+pub const MAX_HAND_SIZE: usize = 8;
+
+pub fn run(input: &str, rules: &Rules) -> Result<u64> {
+    let mut sum: u64 = 0;
+    for hand in rank_hands(input, rules)? {
+        sum = sum
+            .checked_add(hand.winnings)
+            .context("the total winnings overflow")?;
+    }
+    Ok(sum)
+}
+
+/// Lists the ranking of every hand with its winnings, one hand per line.
+pub fn explain(input: &str, rules: &Rules) -> Result<String> {
+    let mut out = format!(
+        "{:>5}  {:<8}  {:<8}  {:<12}  {:>10}  {:>14}\n",
+        "Rank", "Cards", "Played", "Type", "Bid", "Winnings"
+    );
+    let mut sum: u64 = 0;
+    for hand in rank_hands(input, rules)? {
+        out += &format!(
+            "{:>5}  {:<8}  {:<8}  {:<12}  {:>10}  {:>14}\n",
+            hand.position,
+            hand.cards,
+            hand.played,
+            format!("{:?}", hand.rank),
+            hand.bid,
+            hand.winnings
+        );
+        sum = sum
+            .checked_add(hand.winnings)
+            .context("the total winnings overflow")?;
+    }
+    out += &format!("Total winnings: {sum}");
+    Ok(out)
+}
+
+/// A hand in its place in the ranking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankedHand {
+    /// The rank of the hand, starting from 1 for the weakest.
+    pub position: u64,
+    /// The cards as written in the input.
+    pub cards: String,
+    /// The cards with every wild card replaced by the card it plays as.
+    pub played: String,
+    pub rank: RankType,
+    pub bid: u64,
+    pub winnings: u64,
+}
+
+/// Ranks the hands from weakest to strongest.
+pub fn rank_hands(input: &str, rules: &Rules) -> Result<Vec<RankedHand>> {
+    match rules.hand_size {
+        1 => rank_sized_hands::<1>(input, rules),
+        2 => rank_sized_hands::<2>(input, rules),
+        3 => rank_sized_hands::<3>(input, rules),
+        4 => rank_sized_hands::<4>(input, rules),
+        5 => rank_sized_hands::<5>(input, rules),
+        6 => rank_sized_hands::<6>(input, rules),
+        7 => rank_sized_hands::<7>(input, rules),
+        8 => rank_sized_hands::<8>(input, rules),
+        n => Err(anyhow!("unsupported hand size {n}")),
+    }
+}
+
+fn rank_sized_hands<const N: usize>(input: &str, rules: &Rules) -> Result<Vec<RankedHand>> {
+    let mut hands: Vec<(Hand<N>, &str)> = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let hand = parse_hand(line, rules).with_context(|| format!("line {}", i + 1))?;
+            Ok((hand, line.split_whitespace().next().unwrap_or_default()))
+        })
+        .collect::<Result<_>>()?;
+    hands.sort(); // Check out the field order of `Hand`
+
+    hands
+        .into_iter()
+        .zip(1..)
+        .map(|((hand, cards), position)| {
+            Ok(RankedHand {
+                position,
+                cards: cards.to_string(),
+                played: rules
+                    .substitute(hand.cards)
+                    .iter()
+                    .map(Card::to_string)
+                    .collect(),
+                rank: hand.rank,
+                bid: hand.bid,
+                winnings: hand
+                    .bid
+                    .checked_mul(position)
+                    .with_context(|| format!("the winnings of `{cards}` overflow"))?,
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Card {
+    /// A wild card that is weaker than every other card.
+    Joker,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Seven,
+    Eight,
+    Nine,
+    Ten,
+    Jack,
+    Queen,
+    King,
+    Ace,
+}
+
+impl Card {
+    /// Every card except the joker, from weakest to strongest.
+    pub const NATURAL: [Card; 13] = [
+        Card::Two,
+        Card::Three,
+        Card::Four,
+        Card::Five,
+        Card::Six,
+        Card::Seven,
+        Card::Eight,
+        Card::Nine,
+        Card::Ten,
+        Card::Jack,
+        Card::Queen,
+        Card::King,
+        Card::Ace,
+    ];
+
+    /// Returns the character of the card. A joker is written as a `J`.
+    pub fn to_char(self) -> char {
+        match self {
+            Card::Two => '2',
+            Card::Three => '3',
+            Card::Four => '4',
+            Card::Five => '5',
+            Card::Six => '6',
+            Card::Seven => '7',
+            Card::Eight => '8',
+            Card::Nine => '9',
+            Card::Ten => 'T',
+            Card::Joker | Card::Jack => 'J',
+            Card::Queen => 'Q',
+            Card::King => 'K',
+            Card::Ace => 'A',
+        }
+    }
 }
 
-pub fn run(input: &str, is_v2: bool) -> i32 {
-    let mut hands: Vec<Hand> = input.lines().map(|line| parse_hand(line, is_v2)).collect();
-    hands.sort_by(|a, b| a.cmp(&b)); // Check out `impl Ord for Hand`
+impl TryFrom<char> for Card {
+    type Error = anyhow::Error;
 
-    /*for hand in &hands {
-        println!("{} => {:?} => {}", hand.cards, hand.rank, hand.bid);
-    }*/
+    /// Parses a natural card. A `J` is a jack, which the rules may turn into a joker.
+    fn try_from(c: char) -> Result<Self> {
+        Card::NATURAL
+            .into_iter()
+            .find(|card| card.to_char() == c)
+            .ok_or_else(|| anyhow!("invalid card `{c}`"))
+    }
+}
 
-    let mut sum = 0;
-    for (i, Hand { bid, .. }) in hands.iter().enumerate() {
-        sum += bid * (i as i32 + 1)
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_char())
     }
-    sum
 }
 
-#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// The type of a hand. Straights and flushes only exist in poker.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum RankType {
     #[default]
     HighCard,
     OnePair,
     TwoPair,
     ThreeOfAKind,
+    Straight,
+    Flush,
     FullHouse,
     FourOfAKind,
+    StraightFlush,
     FiveOfAKind,
 }
 
-pub fn get_rank(hand: &str) -> RankType {
+/// Ranks a hand by taking every card at face value.
+///
+/// Hands with more than five cards rank by their two largest groups,
+/// so six of a kind is still a five of a kind and 3+3 is a full house.
+pub fn get_rank(hand: &[Card]) -> RankType {
     let mut rank_counts = HashMap::new();
 
-    for card in hand.chars() {
+    for card in hand {
         *rank_counts.entry(card).or_insert(0) += 1;
     }
 
-    let max_rank_count = rank_counts.values().max().unwrap_or(&0);
-    let pairs = rank_counts.values().filter(|&&c| c == 2).count();
+    let mut counts: Vec<usize> = rank_counts.into_values().collect();
+    counts.sort_unstable_by(|a, b| b.cmp(a));
 
-    match max_rank_count {
-        5 => RankType::FiveOfAKind,
-        4 => RankType::FourOfAKind,
-        3 if pairs > 0 => RankType::FullHouse,
-        3 => RankType::ThreeOfAKind,
+    let pairs = counts.iter().filter(|&&c| c == 2).count();
+    match counts[..] {
+        [largest, ..] if largest >= 5 => RankType::FiveOfAKind,
+        [4, ..] => RankType::FourOfAKind,
+        [3, second, ..] if second >= 2 => RankType::FullHouse,
+        [3, ..] => RankType::ThreeOfAKind,
         _ => match pairs {
-            2 => RankType::TwoPair,
+            0 => RankType::HighCard,
             1 => RankType::OnePair,
-            _ => RankType::HighCard,
+            _ => RankType::TwoPair,
         },
     }
 }
 
-#[derive(Debug, Default, PartialEq, Eq, PartialOrd)]
-pub struct Hand {
-    cards: String,
-    rank: RankType,
-    bid: i32,
+/// Where wild cards sit in the card ordering when breaking ties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WildRank {
+    /// Wild cards are weaker than every other card.
+    Lowest,
+    /// Wild cards keep their place in the card ordering.
+    Natural,
 }
 
-impl Hand {
-    fn new(cards: String, rank: RankType, bid: i32) -> Self {
-        Hand { cards, rank, bid }
-    }
+/// How two hands of the same type are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tiebreak {
+    /// Compare the cards in the order they were dealt.
+    Dealt,
+    /// Compare the cards from strongest to weakest, as in poker.
+    Sorted,
 }
 
-impl Ord for Hand {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.cards == other.cards {
-            Ordering::Equal
-        } else if self.rank > other.rank {
-            Ordering::Greater
-        } else if self.rank < other.rank {
-            Ordering::Less
+/// A set of Camel Cards rules.
+///
+/// Rules can be written as a small spec with one `key: value` per line,
+/// where blank lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// order: 23456789TJQKA
+/// wild: JQ
+/// wild rank: lowest
+/// hand size: 5
+/// tiebreak: dealt
+/// ```
+///
+/// Every key is optional and defaults to the part one rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    /// The cards from weakest to strongest.
+    pub order: Vec<Card>,
+    /// The cards that count as any other card when ranking a hand.
+    /// With `WildRank::Lowest` they are dealt as jokers.
+    pub wild: Vec<Card>,
+    pub wild_rank: WildRank,
+    pub hand_size: usize,
+    pub tiebreak: Tiebreak,
+}
+
+impl Rules {
+    pub fn part_one() -> Self {
+        Rules {
+            order: Card::NATURAL.to_vec(),
+            wild: vec![],
+            wild_rank: WildRank::Natural,
+            hand_size: 5,
+            tiebreak: Tiebreak::Dealt,
+        }
+    }
+
+    /// Jacks are jokers: they are wild and the weakest card.
+    pub fn part_two() -> Self {
+        Rules {
+            wild: vec![Card::Jack],
+            wild_rank: WildRank::Lowest,
+            ..Rules::part_one()
+        }
+    }
+
+    /// Returns the card a player holds when `card` is dealt:
+    /// a joker if it is wild and the weakest card, and `card` itself otherwise.
+    pub fn deal(&self, card: Card) -> Card {
+        if self.wild_rank == WildRank::Lowest && self.wild.contains(&card) {
+            Card::Joker
         } else {
-            for (a, b) in self.cards.chars().map(card_value).zip(other.cards.chars().map(card_value)) {
-                if a == b {
-                    continue;
+            card
+        }
+    }
+
+    pub fn is_wild(&self, card: Card) -> bool {
+        card == Card::Joker || self.wild.contains(&card)
+    }
+
+    /// Returns the strength of a card used to break ties,
+    /// or `None` if the card is not in the ordering.
+    pub fn strength(&self, card: Card) -> Option<u8> {
+        if card == Card::Joker {
+            return Some(0);
+        }
+        let position = self.order.iter().position(|&c| c == card)?;
+        Some(position as u8 + 1)
+    }
+
+    /// Replaces every wild card with the card that gives the best hand.
+    ///
+    /// Only the cards already in the hand are worth copying, and copying the
+    /// same card into every wild slot is never worse than splitting them up,
+    /// so trying each of those cards finds the best hand. Ties go to the
+    /// strongest card, and a hand of only wild cards becomes the strongest card.
+    pub fn substitute<const N: usize>(&self, cards: [Card; N]) -> [Card; N] {
+        let strongest = self.order.iter().copied().rev().find(|&c| !self.is_wild(c));
+        cards
+            .iter()
+            .copied()
+            .filter(|&c| !self.is_wild(c))
+            .chain(strongest)
+            .map(|candidate| {
+                let hand = cards.map(|c| if self.is_wild(c) { candidate } else { c });
+                (get_rank(&hand), self.strength(candidate).unwrap_or(0), hand)
+            })
+            .max_by_key(|&(rank, strength, _)| (rank, strength))
+            .map_or(cards, |(_, _, hand)| hand)
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules::part_one()
+    }
+}
+
+impl FromStr for Rules {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut rules = Rules::part_one();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(':').with_context(|| {
+                format!("line {}: expected `key: value`, found `{line}`", i + 1)
+            })?;
+            let value = value.trim();
+            let cards = || {
+                value
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(Card::try_from)
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(|| format!("line {}", i + 1))
+            };
+            match key.trim() {
+                "order" => rules.order = cards()?,
+                "wild" => rules.wild = cards()?,
+                "wild rank" => {
+                    rules.wild_rank = match value {
+                        "lowest" => WildRank::Lowest,
+                        "natural" => WildRank::Natural,
+                        _ => return Err(anyhow!("line {}: unknown wild rank `{value}`", i + 1)),
+                    }
+                }
+                "hand size" => {
+                    rules.hand_size = value
+                        .parse()
+                        .with_context(|| format!("line {}: invalid hand size `{value}`", i + 1))?
                 }
-                return a.cmp(&b);
+                "tiebreak" => {
+                    rules.tiebreak = match value {
+                        "dealt" => Tiebreak::Dealt,
+                        "sorted" => Tiebreak::Sorted,
+                        _ => return Err(anyhow!("line {}: unknown tiebreak `{value}`", i + 1)),
+                    }
+                }
+                key => return Err(anyhow!("line {}: unknown key `{key}`", i + 1)),
             }
-            Ordering::Equal
+        }
+
+        if !(1..=MAX_HAND_SIZE).contains(&rules.hand_size) {
+            return Err(anyhow!("hand size must be between 1 and {MAX_HAND_SIZE}"));
+        }
+        for (i, card) in rules.order.iter().enumerate() {
+            if rules.order[..i].contains(card) {
+                return Err(anyhow!("card `{card}` appears twice in the order"));
+            }
+        }
+        if let Some(card) = rules.wild.iter().find(|card| !rules.order.contains(card)) {
+            return Err(anyhow!("wild card `{card}` is not in the order"));
+        }
+        Ok(rules)
+    }
+}
+
+/// A hand whose fields are ordered so that the derived `Ord` ranks it:
+/// by type first, then by the strengths of the cards as the rules compare them.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hand<const N: usize> {
+    rank: RankType,
+    strengths: [u8; N],
+    cards: [Card; N],
+    bid: u64,
+}
+
+impl<const N: usize> Hand<N> {
+    /// Values dealt cards under the rules.
+    fn new(cards: [Card; N], bid: u64, rules: &Rules) -> Result<Self> {
+        let mut strengths = [0; N];
+        for (strength, &card) in strengths.iter_mut().zip(&cards) {
+            *strength = rules
+                .strength(card)
+                .with_context(|| format!("card `{card}` is not in the order"))?;
+        }
+        if rules.tiebreak == Tiebreak::Sorted {
+            strengths.sort_unstable_by(|a, b| b.cmp(a));
+        }
+
+        let rank = get_rank(&rules.substitute(cards));
+        Ok(Hand {
+            rank,
+            strengths,
+            cards,
+            bid,
+        })
+    }
+}
+
+/// Parses cards like `KTJJT` and deals them under the rules.
+fn parse_cards<const N: usize>(s: &str, rules: &Rules) -> Result<[Card; N]> {
+    let cards: Vec<Card> = s
+        .chars()
+        .map(|c| Card::try_from(c).map(|card| rules.deal(card)))
+        .collect::<Result<_>>()?;
+    cards
+        .try_into()
+        .map_err(|cards: Vec<Card>| anyhow!("expected {N} cards, found {}", cards.len()))
+}
+
+fn parse_hand<const N: usize>(s: &str, rules: &Rules) -> Result<Hand<N>> {
+    let [cards, bid] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err(anyhow!("expected cards and a bid, found `{s}`"));
+    };
+    let bid = bid
+        .parse()
+        .with_context(|| format!("invalid bid `{bid}`"))?;
+
+    Hand::new(parse_cards(cards, rules)?, bid, rules)
+}
+
+/// Returns the number of different hands that can be dealt under the rules.
+pub fn hands(rules: &Rules) -> u64 {
+    (rules.order.len() as u64).pow(rules.hand_size as u32)
+}
+
+/// Calls `f` once for every way to deal `N` cards up to their order, with the
+/// cards sorted and the number of hands that deal them in each order.
+///
+/// That number is more than one when several cards deal as jokers.
+fn for_each_deal<const N: usize>(rules: &Rules, mut f: impl FnMut([Card; N], u64)) {
+    let mut alphabet: Vec<(Card, u64)> = vec![];
+    for &card in &rules.order {
+        match alphabet.iter_mut().find(|(c, _)| *c == rules.deal(card)) {
+            Some((_, weight)) => *weight += 1,
+            None => alphabet.push((rules.deal(card), 1)),
         }
     }
+
+    fn go<const N: usize>(
+        alphabet: &[(Card, u64)],
+        cards: &mut Vec<Card>,
+        weight: u64,
+        f: &mut impl FnMut([Card; N], u64),
+    ) {
+        if cards.len() == N {
+            f(cards[..].try_into().unwrap(), weight);
+            return;
+        }
+        for (i, &(card, card_weight)) in alphabet.iter().enumerate() {
+            cards.push(card);
+            go(&alphabet[i..], cards, weight * card_weight, f);
+            cards.pop();
+        }
+    }
+    go(&alphabet, &mut Vec::with_capacity(N), 1, &mut f);
+}
+
+/// Returns the number of distinct orders of a multiset with these counts.
+fn orderings(counts: &HashMap<u8, usize>) -> u64 {
+    let factorial = |n: usize| (1..=n as u64).product::<u64>();
+    let n = counts.values().sum();
+    counts
+        .values()
+        .fold(factorial(n), |acc, &c| acc / factorial(c))
+}
+
+/// Counts the orders of the strengths in `counts` that are lexicographically
+/// less than `target`, by fixing each prefix of `target` in turn.
+fn orderings_below(mut counts: HashMap<u8, usize>, target: &[u8]) -> u64 {
+    let mut below = 0;
+    for strength in target {
+        let smaller: Vec<u8> = counts
+            .iter()
+            .filter(|&(s, &c)| s < strength && c > 0)
+            .map(|(&s, _)| s)
+            .collect();
+        for s in smaller {
+            *counts.get_mut(&s).unwrap() -= 1;
+            below += orderings(&counts);
+            *counts.get_mut(&s).unwrap() += 1;
+        }
+        match counts.get_mut(strength) {
+            Some(c) if *c > 0 => *c -= 1,
+            _ => break,
+        }
+    }
+    below
+}
+
+/// Returns how many hands there are of each type under the rules.
+pub fn distribution<const N: usize>(rules: &Rules) -> BTreeMap<RankType, u64> {
+    let mut distribution = BTreeMap::new();
+    for_each_deal::<N>(rules, |cards, weight| {
+        let mut counts = HashMap::new();
+        for card in cards {
+            *counts.entry(card as u8).or_insert(0) += 1;
+        }
+        *distribution
+            .entry(get_rank(&rules.substitute(cards)))
+            .or_insert(0) += orderings(&counts) * weight;
+    });
+    distribution
+}
+
+/// How a hand compares to every hand that can be dealt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Equity {
+    pub weaker: u64,
+    pub ties: u64,
+    pub stronger: u64,
 }
 
-fn apply_wildcards(s: &str) -> String {
-    if s == "JJJJJ" {
-        return "KKKKK".to_string();
+impl Equity {
+    pub fn total(&self) -> u64 {
+        self.weaker + self.ties + self.stronger
+    }
+
+    /// Returns the percentage of hands that the hand beats, counting ties as half.
+    pub fn percentile(&self) -> f64 {
+        100.0 * (self.weaker as f64 + self.ties as f64 / 2.0) / self.total() as f64
     }
-    let wildcard_count = s.chars().filter(|&c| c == 'J').count();
-    if wildcard_count > 0 {
-        let max_card = most_significant_card(s);
-        s.replace('J', &max_card.to_string())
-    } else {
-        s.to_string()
+
+    /// Returns the probability that a uniformly random hand beats the hand.
+    pub fn beaten(&self) -> f64 {
+        self.stronger as f64 / self.total() as f64
     }
 }
 
-fn parse_hand(s: &str, is_v2: bool) -> Hand {
-    if let [cards, bid] = s.split_whitespace().collect::<Vec<_>>()[..] {
-        let bid = bid.parse().unwrap();
+/// Compares a hand against every hand that can be dealt under the rules.
+///
+/// Rather than dealing all of them, this goes through the deals up to order.
+/// Every order of a deal has the same type, and with a sorted tiebreak the same
+/// strengths too, so only dealt-order ties need the orders to be counted.
+pub fn equity<const N: usize>(hand: &Hand<N>, rules: &Rules) -> Equity {
+    let mut equity = Equity {
+        weaker: 0,
+        ties: 0,
+        stronger: 0,
+    };
+    for_each_deal::<N>(rules, |cards, weight| {
+        let other = Hand::new(cards, 0, rules).expect("dealt cards are in the order");
+        let mut counts = HashMap::new();
+        for strength in other.strengths {
+            *counts.entry(strength).or_insert(0) += 1;
+        }
+        let total = orderings(&counts);
 
-        let rank = {
-            if is_v2 {
-                get_rank(&apply_wildcards(cards))
-            } else {
-                get_rank(cards)
-            }
+        let (weaker, ties) = match other.rank.cmp(&hand.rank) {
+            Ordering::Less => (total, 0),
+            Ordering::Greater => (0, 0),
+            Ordering::Equal => match rules.tiebreak {
+                Tiebreak::Sorted => match other.strengths.cmp(&hand.strengths) {
+                    Ordering::Less => (total, 0),
+                    Ordering::Equal => (0, total),
+                    Ordering::Greater => (0, 0),
+                },
+                Tiebreak::Dealt => {
+                    let [mut ours, mut theirs] = [hand.strengths, other.strengths];
+                    ours.sort_unstable();
+                    theirs.sort_unstable();
+                    let ties = u64::from(ours == theirs);
+                    (orderings_below(counts, &hand.strengths), ties)
+                }
+            },
         };
+        equity.weaker += weaker * weight;
+        equity.ties += ties * weight;
+        equity.stronger += (total - weaker - ties) * weight;
+    });
+    equity
+}
 
-        let cards_for_scoring = if is_v2 {
-            cards.replace('J', "1")
-        } else {
-            cards.to_string()
-        };
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Suit {
+    Clubs,
+    Diamonds,
+    Hearts,
+    Spades,
+}
 
-        Hand::new(cards_for_scoring, rank, bid)
-    } else {
-        println!("Invalid line: {}", s);
-        Default::default()
+impl TryFrom<char> for Suit {
+    type Error = anyhow::Error;
+
+    fn try_from(c: char) -> Result<Self> {
+        match c.to_ascii_lowercase() {
+            'c' => Ok(Suit::Clubs),
+            'd' => Ok(Suit::Diamonds),
+            'h' => Ok(Suit::Hearts),
+            's' => Ok(Suit::Spades),
+            _ => Err(anyhow!("invalid suit `{c}`")),
+        }
     }
 }
 
-pub fn card_value(x: char) -> i32 {
-    match x {
-        'A' => 14,
-        'K' => 13,
-        'Q' => 12,
-        'J' => 11,
-        'T' => 10,
-        '0'..='9' => x as i32 - '0' as i32,
-        _ => 0,
+/// A card from a standard deck, written as its card and suit, e.g. `Th`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PokerCard {
+    pub card: Card,
+    pub suit: Suit,
+}
+
+impl FromStr for PokerCard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(card), Some(suit), None) => Ok(PokerCard {
+                card: Card::try_from(card)?,
+                suit: Suit::try_from(suit)?,
+            }),
+            _ => Err(anyhow!("expected a card and a suit, found `{s}`")),
+        }
     }
 }
 
-/// Returns the character that appears most in a string,
-/// or if there is a tie, the one with the highest card value.
-pub fn most_significant_card(s: &str) -> char {
-    let mut counts = HashMap::new();
-    for ch in s.chars() {
-        if ch != 'J' {
-            *counts.entry(ch).or_insert(0) += 1;
+/// The value of a five-card poker hand: its type, then the strengths of
+/// its cards from the most to the least important for breaking ties.
+/// In an ace-low straight the ace has strength 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PokerValue {
+    pub rank: RankType,
+    pub kickers: [u8; 5],
+}
+
+/// Values a poker hand from its cards and whether they all share a suit.
+///
+/// This is the straightforward evaluator that `PokerEvaluator` is built from.
+pub fn poker_value(cards: [Card; 5], flush: bool) -> PokerValue {
+    let mut rank_counts = HashMap::new();
+    for card in cards {
+        *rank_counts.entry(card as u8).or_insert(0) += 1;
+    }
+    // Bigger groups come first, and higher cards within groups of the same size.
+    let mut groups: Vec<(usize, u8)> = rank_counts
+        .into_iter()
+        .map(|(card, count)| (count, card))
+        .collect();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut kickers = [0; 5];
+    let mut expanded = groups
+        .iter()
+        .flat_map(|&(count, card)| std::iter::repeat_n(card, count));
+    kickers.fill_with(|| expanded.next().unwrap_or(0));
+
+    let wheel = [Card::Ace, Card::Five, Card::Four, Card::Three, Card::Two].map(|c| c as u8);
+    let straight = groups.len() == 5 && (kickers[0] - kickers[4] == 4 || kickers == wheel);
+    if kickers == wheel {
+        kickers = [Card::Five, Card::Four, Card::Three, Card::Two, Card::Joker].map(|c| c as u8);
+    }
+
+    // Straights and flushes only beat the groups below a full house.
+    let rank = match get_rank(&cards) {
+        _ if straight && flush => RankType::StraightFlush,
+        rank if rank >= RankType::FullHouse => rank,
+        _ if flush => RankType::Flush,
+        _ if straight => RankType::Straight,
+        rank => rank,
+    };
+    PokerValue { rank, kickers }
+}
+
+/// A lookup-table poker evaluator.
+///
+/// There are only 7462 distinct values among the 2,598,960 five-card hands.
+/// They are numbered from weakest to strongest once, up front, so valuing a
+/// hand is a single table lookup: flushes are looked up by the set of their
+/// cards, and every other hand by the product of one prime per card, which
+/// is the same for every order of the cards.
+pub struct PokerEvaluator {
+    flushes: Vec<u16>,
+    others: HashMap<u32, u16>,
+    values: Vec<PokerValue>,
+}
+
+const PRIMES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+fn prime(card: Card) -> u32 {
+    PRIMES[card as usize - 1]
+}
+
+fn card_bits(cards: &[Card; 5]) -> usize {
+    cards
+        .iter()
+        .fold(0, |bits, &card| bits | 1 << (card as usize - 1))
+}
+
+impl PokerEvaluator {
+    pub fn new() -> Self {
+        let mut hands = vec![];
+        // Every multiset of five cards with at most four of a kind.
+        for a in 0..13 {
+            for b in a..13 {
+                for c in b..13 {
+                    for d in c..13 {
+                        for e in d..13 {
+                            if a == e {
+                                continue;
+                            }
+                            let cards = [a, b, c, d, e].map(|i| Card::NATURAL[i]);
+                            hands.push((cards, false));
+                            if a < b && b < c && c < d && d < e {
+                                hands.push((cards, true));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut values: Vec<PokerValue> = hands
+            .iter()
+            .map(|&(cards, flush)| poker_value(cards, flush))
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut flushes = vec![0; 1 << 13];
+        let mut others = HashMap::new();
+        for (cards, flush) in hands {
+            let value = poker_value(cards, flush);
+            let class = values.binary_search(&value).unwrap() as u16;
+            if flush {
+                flushes[card_bits(&cards)] = class;
+            } else {
+                others.insert(cards.iter().map(|&card| prime(card)).product(), class);
+            }
+        }
+        PokerEvaluator {
+            flushes,
+            others,
+            values,
+        }
+    }
+
+    /// Returns the class of the hand, from 0 for the weakest to 7461 for a royal flush.
+    pub fn class(&self, hand: &[PokerCard; 5]) -> u16 {
+        let cards = hand.map(|c| c.card);
+        if hand.iter().all(|c| c.suit == hand[0].suit) {
+            self.flushes[card_bits(&cards)]
+        } else {
+            self.others[&cards.iter().map(|&card| prime(card)).product()]
         }
     }
 
-    let mut max_char = '\0';
-    let mut max_count = 0;
-    let mut max_value = 0;
+    pub fn value(&self, hand: &[PokerCard; 5]) -> PokerValue {
+        self.values[self.class(hand) as usize]
+    }
+
+    /// Returns every distinct hand value, from weakest to strongest.
+    pub fn values(&self) -> &[PokerValue] {
+        &self.values
+    }
+}
 
-    for (&ch, &count) in &counts {
-        let value = card_value(ch);
-        if count > max_count || (count == max_count && value > max_value) {
-            max_count = count;
-            max_value = value;
-            max_char = ch;
+impl Default for PokerEvaluator {
+    fn default() -> Self {
+        PokerEvaluator::new()
+    }
+}
+
+fn parse_poker_hand(s: &str) -> Result<([PokerCard; 5], u64)> {
+    let [cards, bid] = s.split_whitespace().collect::<Vec<_>>()[..] else {
+        return Err(anyhow!("expected cards and a bid, found `{s}`"));
+    };
+    let bid = bid
+        .parse()
+        .with_context(|| format!("invalid bid `{bid}`"))?;
+
+    let chars: Vec<char> = cards.chars().collect();
+    let cards: Vec<PokerCard> = chars
+        .chunks(2)
+        .map(|pair| pair.iter().collect::<String>().parse())
+        .collect::<Result<_>>()?;
+    let cards: [PokerCard; 5] = cards
+        .try_into()
+        .map_err(|cards: Vec<PokerCard>| anyhow!("expected 5 cards, found {}", cards.len()))?;
+    for (i, card) in cards.iter().enumerate() {
+        if cards[..i].contains(card) {
+            return Err(anyhow!(
+                "card `{}` is dealt twice",
+                chars[2 * i..2 * i + 2].iter().collect::<String>()
+            ));
         }
     }
+    Ok((cards, bid))
+}
+
+/// Computes the winnings of hands like `AhKhQhJhTh 765`, ranked by poker rules.
+pub fn run_poker(input: &str) -> Result<u64> {
+    let evaluator = PokerEvaluator::new();
+    let mut hands: Vec<(u16, u64)> = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let (cards, bid) = parse_poker_hand(line).with_context(|| format!("line {}", i + 1))?;
+            Ok((evaluator.class(&cards), bid))
+        })
+        .collect::<Result<_>>()?;
+    hands.sort();
 
-    max_char
+    let mut sum: u64 = 0;
+    for (&(_, bid), position) in hands.iter().zip(1..) {
+        sum = bid
+            .checked_mul(position)
+            .and_then(|winnings| sum.checked_add(winnings))
+            .context("the total winnings overflow")?;
+    }
+    Ok(sum)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{run, parse_hand, RankType};
-    
+    use std::collections::HashMap;
+
+    use super::{
+        distribution, equity, explain, get_rank, hands, parse_hand, parse_poker_hand, poker_value,
+        rank_hands, run, run_poker, Card, Equity, Hand, PokerCard, PokerEvaluator, RankType, Rules,
+        Suit, Tiebreak, WildRank,
+    };
+
     const TEST_INPUT: &str = concat!(
         "32T3K 765\n",
         "T55J5 684\n",
@@ -179,46 +930,50 @@ mod tests {
         "QQQJA 483\n",
     );
 
+    fn rank(line: &str, rules: &Rules) -> RankType {
+        parse_hand::<5>(line, rules).unwrap().rank
+    }
+
     #[test]
     fn test_ranking_v1() {
-        assert_eq!(parse_hand("J2345 0", false).rank, RankType::HighCard);
-        assert_eq!(parse_hand("JJ234 0", false).rank, RankType::OnePair);
-        assert_eq!(parse_hand("JJ224 0", false).rank, RankType::TwoPair);
-        assert_eq!(parse_hand("J3222 0", false).rank, RankType::ThreeOfAKind);
-        assert_eq!(parse_hand("JJ222 0", false).rank, RankType::FullHouse);
-        assert_eq!(parse_hand("J2222 0", false).rank, RankType::FourOfAKind);
-        assert_eq!(parse_hand("22222 0", false).rank, RankType::FiveOfAKind);
+        assert_eq!(rank("J2345 0", &Rules::part_one()), RankType::HighCard);
+        assert_eq!(rank("JJ234 0", &Rules::part_one()), RankType::OnePair);
+        assert_eq!(rank("JJ224 0", &Rules::part_one()), RankType::TwoPair);
+        assert_eq!(rank("J3222 0", &Rules::part_one()), RankType::ThreeOfAKind);
+        assert_eq!(rank("JJ222 0", &Rules::part_one()), RankType::FullHouse);
+        assert_eq!(rank("J2222 0", &Rules::part_one()), RankType::FourOfAKind);
+        assert_eq!(rank("22222 0", &Rules::part_one()), RankType::FiveOfAKind);
     }
 
     #[test]
     fn test_ranking_v2() {
-        assert_eq!(parse_hand("K2345 0", true).rank, RankType::HighCard);
-        assert_eq!(parse_hand("KK234 0", true).rank, RankType::OnePair);
-        assert_eq!(parse_hand("KK224 0", true).rank, RankType::TwoPair);
-        assert_eq!(parse_hand("K3222 0", true).rank, RankType::ThreeOfAKind);
-        assert_eq!(parse_hand("KK222 0", true).rank, RankType::FullHouse);
-        assert_eq!(parse_hand("K2222 0", true).rank, RankType::FourOfAKind);
-        assert_eq!(parse_hand("22222 0", true).rank, RankType::FiveOfAKind);
+        assert_eq!(rank("K2345 0", &Rules::part_two()), RankType::HighCard);
+        assert_eq!(rank("KK234 0", &Rules::part_two()), RankType::OnePair);
+        assert_eq!(rank("KK224 0", &Rules::part_two()), RankType::TwoPair);
+        assert_eq!(rank("K3222 0", &Rules::part_two()), RankType::ThreeOfAKind);
+        assert_eq!(rank("KK222 0", &Rules::part_two()), RankType::FullHouse);
+        assert_eq!(rank("K2222 0", &Rules::part_two()), RankType::FourOfAKind);
+        assert_eq!(rank("22222 0", &Rules::part_two()), RankType::FiveOfAKind);
     }
 
     #[test]
     fn test_ranking_with_wild_v2() {
-        assert_eq!(parse_hand("23456 0", true).rank, RankType::HighCard);
-        assert_eq!(parse_hand("J2345 0", true).rank, RankType::OnePair);
-        assert_eq!(parse_hand("J4545 0", true).rank, RankType::FullHouse);
-        assert_eq!(parse_hand("J2355 0", true).rank, RankType::ThreeOfAKind);
-        assert_eq!(parse_hand("J2555 0", true).rank, RankType::FourOfAKind);
-        assert_eq!(parse_hand("J5555 0", true).rank, RankType::FiveOfAKind);
-        assert_eq!(parse_hand("JJ345 0", true).rank, RankType::ThreeOfAKind);
-        assert_eq!(parse_hand("JJ455 0", true).rank, RankType::FourOfAKind);
-        assert_eq!(parse_hand("JJ555 0", true).rank, RankType::FiveOfAKind);
+        assert_eq!(rank("23456 0", &Rules::part_two()), RankType::HighCard);
+        assert_eq!(rank("J2345 0", &Rules::part_two()), RankType::OnePair);
+        assert_eq!(rank("J4545 0", &Rules::part_two()), RankType::FullHouse);
+        assert_eq!(rank("J2355 0", &Rules::part_two()), RankType::ThreeOfAKind);
+        assert_eq!(rank("J2555 0", &Rules::part_two()), RankType::FourOfAKind);
+        assert_eq!(rank("J5555 0", &Rules::part_two()), RankType::FiveOfAKind);
+        assert_eq!(rank("JJ345 0", &Rules::part_two()), RankType::ThreeOfAKind);
+        assert_eq!(rank("JJ455 0", &Rules::part_two()), RankType::FourOfAKind);
+        assert_eq!(rank("JJ555 0", &Rules::part_two()), RankType::FiveOfAKind);
     }
 
     #[test]
     fn test_example_ranking_v1() {
         let mut ranks = vec![];
         for line in TEST_INPUT.lines() {
-            let hand = parse_hand(line, false);
+            let hand = parse_hand::<5>(line, &Rules::part_one()).unwrap();
             ranks.push(hand.rank);
         }
         assert_eq!(ranks, vec![
@@ -234,7 +989,7 @@ mod tests {
     fn test_example_ranking_v2() {
         let mut ranks = vec![];
         for line in TEST_INPUT.lines() {
-            let hand = parse_hand(line, true);
+            let hand = parse_hand::<5>(line, &Rules::part_two()).unwrap();
             ranks.push(hand.rank);
         }
         assert_eq!(ranks, vec![
@@ -248,11 +1003,354 @@ mod tests {
 
     #[test]
     fn test_v1() {
-        assert_eq!(run(TEST_INPUT, false), 6440);
+        assert_eq!(run(TEST_INPUT, &Rules::part_one()).unwrap(), 6440);
     }
 
     #[test]
     fn test_v2() {
-        assert_eq!(run(TEST_INPUT, true), 5905);
+        assert_eq!(run(TEST_INPUT, &Rules::part_two()).unwrap(), 5905);
+    }
+
+    #[test]
+    fn test_rules_spec() {
+        assert_eq!("".parse::<Rules>().unwrap(), Rules::part_one());
+        let spec = "# part two\nwild: J\nwild rank: lowest\n";
+        assert_eq!(spec.parse::<Rules>().unwrap(), Rules::part_two());
+
+        let rules: Rules = concat!(
+            "order: 2 3 4 5 6 7 8 9 T J Q K A\n",
+            "wild: JQ\n",
+            "wild rank: natural\n",
+            "hand size: 6\n",
+            "tiebreak: sorted\n",
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(rules.wild, vec![Card::Jack, Card::Queen]);
+        assert_eq!(rules.wild_rank, WildRank::Natural);
+        assert_eq!(rules.hand_size, 6);
+        assert_eq!(rules.tiebreak, Tiebreak::Sorted);
+
+        assert!("colour: red".parse::<Rules>().is_err());
+        assert!("hand size: five".parse::<Rules>().is_err());
+        assert!("wild: X".parse::<Rules>().is_err());
+        assert!("order: 2234".parse::<Rules>().is_err());
+        assert!("tiebreak: random".parse::<Rules>().is_err());
+    }
+
+    #[test]
+    fn test_house_rules() {
+        let rules: Rules = "wild: JQ\nwild rank: lowest".parse().unwrap();
+        assert_eq!(rank("QJ234 0", &rules), RankType::ThreeOfAKind);
+        assert_eq!(rank("QQQJJ 0", &rules), RankType::FiveOfAKind);
+        // Both wild cards are weaker than a 2.
+        assert_eq!(run("Q2345 1\n22345 2\n", &rules).unwrap(), 5);
+
+        let rules: Rules = "hand size: 6".parse().unwrap();
+        let rank = |line| parse_hand::<6>(line, &rules).unwrap().rank;
+        assert_eq!(rank("222333 0"), RankType::FullHouse);
+        assert_eq!(rank("223344 0"), RankType::TwoPair);
+        assert_eq!(rank("222222 0"), RankType::FiveOfAKind);
+        assert!(run("22345 0", &rules).is_err());
+    }
+
+    #[test]
+    fn test_sorted_tiebreak() {
+        // Dealt order favours the hand that starts with a K, sorted order the one holding an A.
+        let input = "K2345 1\n2345A 2\n";
+        assert_eq!(run(input, &Rules::part_one()).unwrap(), 2 + 2);
+        let rules: Rules = "tiebreak: sorted".parse().unwrap();
+        assert_eq!(run(input, &rules).unwrap(), 1 + 4);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| format!("{:#}", run(input, &Rules::part_one()).unwrap_err());
+        assert_eq!(error("32T3K 765\n32X3K 1\n"), "line 2: invalid card `X`");
+        assert_eq!(error("32T3 765\n"), "line 1: expected 5 cards, found 4");
+        assert_eq!(
+            error("32T3K -\n"),
+            "line 1: invalid bid `-`: invalid digit found in string"
+        );
+        assert_eq!(
+            error("32T3K\n"),
+            "line 1: expected cards and a bid, found `32T3K`"
+        );
+    }
+
+    #[test]
+    fn test_jokers() {
+        let rules = Rules::part_two();
+        let hand = parse_hand::<5>("JJJJJ 0", &rules).unwrap();
+        assert_eq!(hand.cards, [Card::Joker; 5]);
+        assert_eq!(hand.rank, RankType::FiveOfAKind);
+        // Five jokers are weaker than any other five of a kind.
+        assert!(hand < parse_hand("22222 0", &rules).unwrap());
+        assert!(hand > parse_hand("AAAAK 0", &rules).unwrap());
+    }
+
+    #[test]
+    fn test_substitute_ties() {
+        // The joker can join any of the cards for a pair, or either pair for
+        // a full house, and picks the strongest whatever the order.
+        let input = "5432J 1\n2345J 2\nK2K2J 3\n2K2KJ 4\n";
+        let played: Vec<String> = rank_hands(input, &Rules::part_two())
+            .unwrap()
+            .into_iter()
+            .map(|hand| hand.played)
+            .collect();
+        assert_eq!(played, ["23455", "54325", "2K2KK", "K2K2K"]);
+    }
+
+    #[test]
+    fn test_substitute_brute_force() {
+        // Every hand of jokers and three cards, against every way of replacing the jokers.
+        let rules = Rules::part_two();
+        let cards = [Card::Joker, Card::Two, Card::Three, Card::Ace];
+        for i in 0..cards.len().pow(5) {
+            let hand: [Card; 5] =
+                std::array::from_fn(|j| cards[i / cards.len().pow(j as u32) % cards.len()]);
+            let jokers: Vec<usize> = (0..5).filter(|&j| hand[j] == Card::Joker).collect();
+            let best = (0..Card::NATURAL.len().pow(jokers.len() as u32))
+                .map(|k| {
+                    let mut hand = hand;
+                    for (n, &j) in jokers.iter().enumerate() {
+                        hand[j] = Card::NATURAL
+                            [k / Card::NATURAL.len().pow(n as u32) % Card::NATURAL.len()];
+                    }
+                    get_rank(&hand)
+                })
+                .max()
+                .unwrap();
+            assert_eq!(get_rank(&rules.substitute(hand)), best, "{hand:?}");
+        }
+    }
+
+    fn poker_hand(cards: &str) -> [PokerCard; 5] {
+        parse_poker_hand(&format!("{cards} 0")).unwrap().0
+    }
+
+    /// Every five-card hand from a standard deck.
+    fn all_poker_hands() -> impl Iterator<Item = [PokerCard; 5]> {
+        let suits = [Suit::Clubs, Suit::Diamonds, Suit::Hearts, Suit::Spades];
+        let deck: Vec<PokerCard> = Card::NATURAL
+            .iter()
+            .flat_map(|&card| suits.map(|suit| PokerCard { card, suit }))
+            .collect();
+        (0..52).flat_map(move |a| {
+            let deck = deck.clone();
+            (a + 1..52).flat_map(move |b| {
+                let deck = deck.clone();
+                (b + 1..52).flat_map(move |c| {
+                    let deck = deck.clone();
+                    (c + 1..52).flat_map(move |d| {
+                        let deck = deck.clone();
+                        (d + 1..52).map(move |e| [a, b, c, d, e].map(|i| deck[i]))
+                    })
+                })
+            })
+        })
+    }
+
+    #[test]
+    fn test_poker_ranking() {
+        let evaluator = PokerEvaluator::new();
+        let rank = |cards| evaluator.value(&poker_hand(cards)).rank;
+        assert_eq!(rank("AhKhQhJhTh"), RankType::StraightFlush);
+        assert_eq!(rank("5c4c3c2cAc"), RankType::StraightFlush);
+        assert_eq!(rank("9s9h9d9c2s"), RankType::FourOfAKind);
+        assert_eq!(rank("9s9h9d2c2s"), RankType::FullHouse);
+        assert_eq!(rank("Kd9d7d5d2d"), RankType::Flush);
+        assert_eq!(rank("Ah2c3d4s5s"), RankType::Straight);
+        assert_eq!(rank("QhKcJdTsAs"), RankType::Straight);
+        assert_eq!(rank("KhAc2d3s4s"), RankType::HighCard);
+        assert_eq!(rank("9s9h9d3c2s"), RankType::ThreeOfAKind);
+        assert_eq!(rank("9s9h3d3c2s"), RankType::TwoPair);
+        assert_eq!(rank("9s9h4d3c2s"), RankType::OnePair);
+        assert_eq!(rank("9s8h4d3c2s"), RankType::HighCard);
+
+        let class = |cards| evaluator.class(&poker_hand(cards));
+        // The ace-low straight is the weakest straight.
+        assert!(class("Ah2c3d4s5s") < class("2h3c4d5s6s"));
+        assert!(class("Ah2c3d4s5s") > class("AhAcAd4s5s"));
+        assert!(class("5c4c3c2cAc") < class("6c5c4c3c2c"));
+        // Kickers break ties, and suits do not.
+        assert!(class("9s9hKd3c2s") > class("9s9hQdJcTs"));
+        assert!(class("9s9h3d3cAs") > class("9s9h3d3cKs"));
+        assert_eq!(class("9s9h3d3cAs"), class("9c9d3h3sAd"));
+        assert!(class("AdKd9d7d5d") > class("AdKd9d7d4d"));
+    }
+
+    #[test]
+    fn test_poker_counts() {
+        let evaluator = PokerEvaluator::new();
+        assert_eq!(evaluator.values().len(), 7462);
+
+        let mut class_counts = vec![0; 7462];
+        for hand in all_poker_hands() {
+            class_counts[evaluator.class(&hand) as usize] += 1;
+        }
+        let mut counts = HashMap::new();
+        for (value, count) in evaluator.values().iter().zip(class_counts) {
+            *counts.entry(value.rank).or_insert(0) += count;
+        }
+        let expected = [
+            (RankType::HighCard, 1302540),
+            (RankType::OnePair, 1098240),
+            (RankType::TwoPair, 123552),
+            (RankType::ThreeOfAKind, 54912),
+            (RankType::Straight, 10200),
+            (RankType::Flush, 5108),
+            (RankType::FullHouse, 3744),
+            (RankType::FourOfAKind, 624),
+            (RankType::StraightFlush, 40),
+        ];
+        assert_eq!(counts, HashMap::from(expected));
+        assert_eq!(counts.values().sum::<usize>(), 2598960);
+    }
+
+    #[test]
+    #[ignore = "checks all 2,598,960 hands, run with `cargo test --release -- --ignored`"]
+    fn test_poker_evaluator_matches_poker_value() {
+        let evaluator = PokerEvaluator::new();
+        for hand in all_poker_hands() {
+            let flush = hand.iter().all(|c| c.suit == hand[0].suit);
+            assert_eq!(
+                evaluator.value(&hand),
+                poker_value(hand.map(|c| c.card), flush),
+                "{hand:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_poker() {
+        let input = "2h3c4d5s6s 10\nAhAcAd4s5s 20\n5c4c3c2cAc 30\nAh2c3d4s5s 40\n";
+        assert_eq!(run_poker(input).unwrap(), 20 + 2 * 40 + 3 * 10 + 4 * 30);
+        assert!(run_poker("AhAh3d4s5s 1").is_err());
+        assert!(run_poker("AhKh3d4s 1").is_err());
+        assert!(run_poker("AhKh3d4x5s 1").is_err());
+    }
+
+    #[test]
+    fn test_distribution() {
+        let rules = Rules::part_one();
+        let expected = [
+            (RankType::HighCard, 154440),
+            (RankType::OnePair, 171600),
+            (RankType::TwoPair, 25740),
+            (RankType::ThreeOfAKind, 17160),
+            (RankType::FullHouse, 1560),
+            (RankType::FourOfAKind, 780),
+            (RankType::FiveOfAKind, 13),
+        ];
+        assert_eq!(
+            distribution::<5>(&rules).into_iter().collect::<Vec<_>>(),
+            expected
+        );
+
+        // Jokers only ever improve a hand, so there are fewer weak hands.
+        for rules in [
+            Rules::part_two(),
+            "wild: JQ\nwild rank: lowest".parse().unwrap(),
+        ] {
+            let distribution = distribution::<5>(&rules);
+            assert_eq!(distribution.values().sum::<u64>(), hands(&rules));
+            assert!(distribution[&RankType::HighCard] < 154440);
+            assert!(distribution[&RankType::FiveOfAKind] > 13);
+        }
+    }
+
+    /// Compares a hand against every dealt hand one by one.
+    fn brute_force_equity<const N: usize>(hand: &Hand<N>, rules: &Rules) -> Equity {
+        let mut equity = Equity {
+            weaker: 0,
+            ties: 0,
+            stronger: 0,
+        };
+        let n = rules.order.len();
+        for i in 0..n.pow(N as u32) {
+            let cards = std::array::from_fn(|j| rules.deal(rules.order[i / n.pow(j as u32) % n]));
+            let other = Hand::new(cards, hand.bid, rules).unwrap();
+            match (other.rank, other.strengths).cmp(&(hand.rank, hand.strengths)) {
+                std::cmp::Ordering::Less => equity.weaker += 1,
+                std::cmp::Ordering::Equal => equity.ties += 1,
+                std::cmp::Ordering::Greater => equity.stronger += 1,
+            }
+        }
+        equity
+    }
+
+    #[test]
+    fn test_equity_brute_force() {
+        let specs = [
+            "hand size: 3",
+            "hand size: 3\nwild: J\nwild rank: lowest",
+            "hand size: 3\nwild: JQ\nwild rank: lowest",
+            "hand size: 3\nwild: Q\ntiebreak: sorted",
+        ];
+        for spec in specs {
+            let rules: Rules = spec.parse().unwrap();
+            for cards in ["222", "JJJ", "QJ2", "2QA", "AKQ", "T9T", "JQK", "A2J"] {
+                let hand = parse_hand::<3>(&format!("{cards} 0"), &rules).unwrap();
+                assert_eq!(
+                    equity(&hand, &rules),
+                    brute_force_equity(&hand, &rules),
+                    "{spec}: {cards}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_equity() {
+        let rules = Rules::part_one();
+        let equity = equity(&parse_hand::<5>("AAAAA 0", &rules).unwrap(), &rules);
+        assert_eq!(equity.stronger, 0);
+        assert_eq!(equity.ties, 1);
+        assert_eq!(equity.total(), 371293);
+
+        let rules = Rules::part_two();
+        let hand = parse_hand::<5>("22222 0", &rules).unwrap();
+        // Only five of a kinds of a stronger card beat it, and only if they start with that card.
+        let stronger = 11 * 2u64.pow(4);
+        assert_eq!(super::equity(&hand, &rules).stronger, stronger);
+        assert_eq!(
+            super::equity(&hand, &rules).beaten(),
+            stronger as f64 / 371293.0
+        );
+    }
+
+    #[test]
+    fn test_winnings_overflow() {
+        // The total overflows an `i32`, but not a `u64`.
+        let input = "22345 2000000000\n33456 2000000000\n";
+        assert_eq!(run(input, &Rules::part_one()).unwrap(), 6000000000);
+
+        let input = format!("22345 {}\n33456 1\n", u64::MAX);
+        assert_eq!(
+            run(&input, &Rules::part_one()).unwrap_err().to_string(),
+            "the total winnings overflow"
+        );
+        let input = format!("22345 1\n33456 {}\n", u64::MAX);
+        assert_eq!(
+            run(&input, &Rules::part_one()).unwrap_err().to_string(),
+            "the winnings of `33456` overflow"
+        );
+        assert!(run_poker(&format!("2h3c4d5s6s 1\nAhAcAd4s5s {}\n", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let expected = concat!(
+            " Rank  Cards     Played    Type                 Bid        Winnings\n",
+            "    1  32T3K     32T3K     OnePair              765             765\n",
+            "    2  KK677     KK677     TwoPair               28              56\n",
+            "    3  T55J5     T5555     FourOfAKind          684            2052\n",
+            "    4  QQQJA     QQQQA     FourOfAKind          483            1932\n",
+            "    5  KTJJT     KTTTT     FourOfAKind          220            1100\n",
+            "Total winnings: 5905",
+        );
+        assert_eq!(explain(TEST_INPUT, &Rules::part_two()).unwrap(), expected);
     }
 }
@@ -1,55 +1,259 @@
 use std::fs;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
 
-fn main() {
+use anyhow::{anyhow, Context};
+
+fn main() -> anyhow::Result<()> {
     let input = fs::read_to_string("inputs/8.txt").expect("Failed to read input file");
-    println!("Part one: {}", part_one(&input));
-    println!("Part two: {}", part_two(&input));
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    match &args[..] {
+        [] => {}
+        [flag, path, rest @ ..] if flag == "--export-dot" => {
+            let color_ghosts = match rest {
+                [] => false,
+                [flag] if flag == "--color-ghosts" => true,
+                _ => return Err(anyhow!("usage: day8 [--export-dot FILE [--color-ghosts]]")),
+            };
+            let graph = Graph::parse(&input)?;
+            fs::write(path, to_dot(&graph, color_ghosts))
+                .with_context(|| format!("failed to write `{path}`"))?;
+            return Ok(());
+        }
+        _ => return Err(anyhow!("usage: day8 [--export-dot FILE [--color-ghosts]]")),
+    }
+
+    println!("Part one: {}", part_one(&input)?);
+    match part_two(&input)? {
+        Some(steps) => println!("Part two: {}", steps),
+        None => println!("Part two: the ghosts never all reach a Z at once"),
+    }
+
+    let graph = Graph::parse(&input)?;
+    let cycles = ghost_cycles(&graph)?;
+    if lcm_shortcut_is_valid(&cycles) {
+        println!("Every ghost reaches one Z exactly once per cycle, at its cycle length, so the LCM shortcut is valid");
+    } else {
+        println!("The LCM shortcut is not valid for this input");
+    }
+    Ok(())
 }
 
-fn get_steps<'a>(graph: &'a Graph, mut state: &'a str, terminator: &str) -> usize {
+/// Counts the steps from `start` to the first node ending with `terminator`.
+///
+/// The walk is fully determined by its (node, move index) state, so once a
+/// state repeats without having reached the terminator, it never will.
+fn get_steps(graph: &Graph, start: &str, terminator: &str) -> Result<usize, WalkError> {
+    let is_end = graph.ends_with(terminator);
+    let mut state = graph.start(start)?;
+    let mut seen = vec![false; graph.names.len() * graph.moves.len()];
     let mut n = 0;
     'outer: loop {
-        for mv in &graph.moves {
-            match mv {
-                Move::Left => state = graph.table[state].0.as_str(),
-                Move::Right => state = graph.table[state].1.as_str(),
+        for index in 0..graph.moves.len() {
+            if std::mem::replace(&mut seen[state as usize * graph.moves.len() + index], true) {
+                return Err(WalkError::Unreachable {
+                    start: start.to_string(),
+                    terminator: terminator.to_string(),
+                });
             }
+            let next = graph.step(state, index)?;
             n += 1;
-            if state.ends_with(terminator) {
+            if is_end[next as usize] {
                 break 'outer;
             }
+            graph.check_defined(state, next)?;
+            state = next;
         }
     }
-    n
+    Ok(n)
+}
+
+fn part_one(input: &str) -> anyhow::Result<usize> {
+    let graph = Graph::parse(input)?;
+    Ok(get_steps(&graph, "AAA", "ZZZ")?)
 }
 
-fn part_one(input: &str) -> usize {
-    let graph = Graph::parse(input);
-    get_steps(&graph, "AAA", "ZZZ")
+fn part_two(input: &str) -> anyhow::Result<Option<u64>> {
+    let graph = Graph::parse(input)?;
+    Ok(first_common_hit(&ghost_cycles(&graph)?)?)
 }
 
-fn part_two(input: &str) -> usize {
-    let graph = Graph::parse(input);
-    let states = graph.table.keys().filter(|key| key.ends_with('A')).map(String::as_str).collect::<Vec<&str>>();
-    
-    debug_assert_eq!(
-        graph.table.keys().filter(|key| key.ends_with('A')).count(),
-        graph.table.keys().filter(|key| key.ends_with('Z')).count(),
-    );
+/// Finds the cycle of every ghost, i.e. every node ending with `A`.
+fn ghost_cycles(graph: &Graph) -> Result<Vec<Cycle>, WalkError> {
+    let mut starts = graph
+        .names
+        .iter()
+        .filter(|name| name.ends_with('A'))
+        .map(String::as_str)
+        .collect::<Vec<&str>>();
+    starts.sort_unstable();
+    starts
+        .iter()
+        .map(|start| find_cycle(graph, start, "Z"))
+        .collect()
+}
 
-    let mut steps = vec![];
-    for &state in &states {
-        let n = get_steps(&graph, state, "Z");
-        steps.push(n);
+/// The walk from a start node, which always ends up repeating a
+/// (node, move index) state and from then on goes round in a cycle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    /// The number of steps before the cycle starts.
+    pub prefix: u64,
+    /// The number of steps around the cycle.
+    pub period: u64,
+    /// The steps before the cycle at which the walk is on a terminator.
+    pub prefix_hits: Vec<u64>,
+    /// The steps in the first time round the cycle at which the walk is on a
+    /// terminator. Each of them is hit again every `period` steps.
+    pub cycle_hits: Vec<u64>,
+}
+
+impl Cycle {
+    /// Returns whether the walk is on a terminator after `step` steps.
+    pub fn hits(&self, step: u64) -> bool {
+        if step < self.prefix {
+            self.prefix_hits.contains(&step)
+        } else {
+            let step = self.prefix + (step - self.prefix) % self.period;
+            self.cycle_hits.contains(&step)
+        }
     }
-    lcm(&steps)
 }
 
-/// The least common multiple of two numbers is the lowest
-/// possible number than can be divisible by both numbers.
-pub fn lcm(nums: &[usize]) -> usize {
-    nums.iter().fold(1, |acc, &n| n * acc / gcd(n, acc))
+/// Walks from `start` until a (node, move index) state repeats.
+pub fn find_cycle(graph: &Graph, start: &str, terminator: &str) -> Result<Cycle, WalkError> {
+    let is_end = graph.ends_with(terminator);
+    // The step at which each (node, move index) state was first seen.
+    let mut seen: Vec<Option<u64>> = vec![None; graph.names.len() * graph.moves.len()];
+    let mut hits = vec![];
+    let mut state = graph.start(start)?;
+    let mut step = 0;
+    loop {
+        let index = step as usize % graph.moves.len();
+        let seen = &mut seen[state as usize * graph.moves.len() + index];
+        if let Some(prefix) = *seen {
+            let (prefix_hits, cycle_hits) = hits.into_iter().partition(|&hit| hit < prefix);
+            return Ok(Cycle {
+                prefix,
+                period: step - prefix,
+                prefix_hits,
+                cycle_hits,
+            });
+        }
+        *seen = Some(step);
+        if is_end[state as usize] {
+            hits.push(step);
+        }
+        let next = graph.step(state, index)?;
+        graph.check_defined(state, next)?;
+        state = next;
+        step += 1;
+    }
+}
+
+/// Returns the first step, after at least one, at which every walk is on a terminator.
+///
+/// A step before every walk has entered its cycle must be one of the prefix
+/// hits, so those are checked one by one. After that, each walk is on a
+/// terminator exactly when the step is congruent to one of its cycle hits
+/// modulo its period, and the generalized Chinese remainder theorem combines
+/// those congruences.
+///
+/// Fails if the answer, or a period that it depends on, does not fit in a `u64`.
+pub fn first_common_hit(cycles: &[Cycle]) -> Result<Option<u64>, WalkError> {
+    let Some(entered) = cycles.iter().map(|cycle| cycle.prefix).max() else {
+        return Ok(None);
+    };
+    let entered = entered.max(1);
+
+    let mut early: Vec<u64> = cycles
+        .iter()
+        .flat_map(|cycle| cycle.prefix_hits.iter().copied())
+        .collect();
+    early.sort_unstable();
+    if let Some(step) = early
+        .into_iter()
+        .find(|&step| step >= 1 && cycles.iter().all(|cycle| cycle.hits(step)))
+    {
+        return Ok(Some(step));
+    }
+
+    let mut congruences = vec![(0, 1)];
+    for cycle in cycles {
+        let mut combined = vec![];
+        for &(residue, modulus) in &congruences {
+            for &hit in &cycle.cycle_hits {
+                if let Some(congruence) =
+                    crt((residue, modulus), (hit % cycle.period, cycle.period))?
+                {
+                    combined.push(congruence);
+                }
+            }
+        }
+        combined.sort_unstable();
+        combined.dedup();
+        congruences = combined;
+    }
+
+    let steps = congruences
+        .into_iter()
+        .map(|(residue, modulus)| {
+            // The first step from `entered` on that is congruent to `residue`.
+            let behind = (residue + modulus - entered % modulus) % modulus;
+            entered.checked_add(behind).ok_or(WalkError::Overflow)
+        })
+        .collect::<Result<Vec<u64>, _>>()?;
+    Ok(steps.into_iter().min())
+}
+
+/// Solves `x = a (mod n)` and `x = b (mod m)` for moduli that need not be coprime,
+/// returning the solution as `x = c (mod lcm(n, m))`, or `None` if there is none.
+/// Fails if `lcm(n, m)` does not fit in a `u64`.
+pub fn crt((a, n): (u64, u64), (b, m): (u64, u64)) -> Result<Option<(u64, u64)>, WalkError> {
+    let g = gcd(n, m);
+    let difference = b as i128 - a as i128;
+    if difference % g as i128 != 0 {
+        return Ok(None);
+    }
+    let lcm = n.checked_mul(m / g).ok_or(WalkError::Overflow)?;
+    // n * k = b - a (mod m), so k = (b - a) / g * (n / g)^-1 (mod m / g).
+    let reduced = (m / g) as i128;
+    let inverse = mod_inverse((n / g) as i128 % reduced, reduced);
+    let k = (difference / g as i128 % reduced * inverse).rem_euclid(reduced);
+    let x = (a as i128 + n as i128 * k).rem_euclid(lcm as i128);
+    Ok(Some((x as u64, lcm)))
+}
+
+/// The inverse of `a` modulo `m`, for coprime `a` and `m`, by the extended Euclidean algorithm.
+fn mod_inverse(a: i128, m: i128) -> i128 {
+    let (mut old_r, mut r) = (a, m);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    old_s.rem_euclid(m)
+}
+
+/// Returns whether every ghost is on a terminator exactly at the multiples of
+/// its first hit, which is what makes the LCM of the first hits the answer.
+pub fn lcm_shortcut_is_valid(cycles: &[Cycle]) -> bool {
+    cycles.iter().all(|cycle| {
+        let end = cycle.prefix + cycle.period;
+        match (1..end).find(|&step| cycle.hits(step)) {
+            // The hits repeat every period, so checking one period is enough
+            // once the multiples line up with it.
+            Some(first) => {
+                cycle.period % first == 0
+                    && (1..end).all(|step| cycle.hits(step) == (step % first == 0))
+            }
+            None => false,
+        }
+    })
 }
 
 pub fn gcd<T: Copy + Default + PartialEq + std::ops::Rem<Output = T>>(a: T, b: T) -> T {
@@ -60,52 +264,343 @@ pub fn gcd<T: Copy + Default + PartialEq + std::ops::Rem<Output = T>>(a: T, b: T
     gcd(b, a % b)
 }
 
-#[derive(Debug, Clone)]
-pub enum Move {
-    Left,
-    Right,
+/// A move along the outgoing edge with this index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move(pub usize);
+
+impl Move {
+    pub const LEFT: Move = Move(0);
+    pub const RIGHT: Move = Move(1);
 }
 
+/// Parses a move written as a letter: `L` and `R` for the puzzle's left and right,
+/// or `a`, `b`, `c`, ... for the first, second, third, ... edge.
 fn parse_move(x: char) -> Option<Move> {
     match x {
-        'L' => Some(Move::Left),
-        'R' => Some(Move::Right),
+        'L' => Some(Move::LEFT),
+        'R' => Some(Move::RIGHT),
+        'a'..='z' => Some(Move(x as usize - 'a' as usize)),
         _ => None,
     }
 }
 
+/// Parses a line of moves, either as letters like `LRLL` or `acb`,
+/// or as edge indices separated by spaces or commas like `0 2 1`.
+fn parse_moves(line: &str) -> Result<Vec<Move>, String> {
+    if line.starts_with(|c: char| c.is_ascii_digit()) {
+        line.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|index| !index.is_empty())
+            .map(|index| {
+                index
+                    .parse()
+                    .map(Move)
+                    .map_err(|_| format!("invalid move `{index}`"))
+            })
+            .collect()
+    } else {
+        line.chars()
+            .map(|x| parse_move(x).ok_or_else(|| format!("invalid move `{x}`")))
+            .collect()
+    }
+}
+
+/// The network, with every node name interned as a dense `u32` id.
 #[derive(Default, Clone)]
 pub struct Graph {
     moves: Vec<Move>,
-    table: HashMap<String, (String, String)>
+    names: Vec<String>,
+    ids: HashMap<String, u32>,
+    /// The outgoing edges of each node, in order, or `None` for
+    /// nodes that are pointed at but never defined.
+    edges: Vec<Option<Vec<u32>>>,
 }
 
 impl Graph {
-    fn parse(s: &str) -> Self {
-        let mut table = HashMap::new();
-        let lines = s.lines().filter(|line| !line.is_empty()).collect::<Vec<_>>();
-        if let Some((first, rest)) = lines.split_first() {
-            let moves = first.chars().filter_map(parse_move).collect();
-            for (row, line) in rest.iter().enumerate() {
-                if let [lhs, rhs] = line.split(" = ").collect::<Vec<_>>()[..] {
-                    let key = lhs.to_string();
-                    let from = rhs[1..4].to_string();
-                    let to = rhs[6..9].to_string();
-                    table.insert(key, (from, to));
-                } else {
-                    panic!("Invalid format for line {}: {}", row + 1, line);
+    fn parse(s: &str) -> Result<Self, ParseError> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let Some((row, first)) = lines.next() else {
+            return Err(ParseError::new(1, "expected a line of moves"));
+        };
+        let moves = parse_moves(first).map_err(|message| ParseError::new(row, message))?;
+
+        let mut graph = Graph {
+            moves,
+            ..Default::default()
+        };
+        for (row, line) in lines {
+            let (name, next) = parse_node(line).ok_or_else(|| {
+                ParseError::new(
+                    row,
+                    format!("expected `NAME = (NEXT, ...)`, found `{line}`"),
+                )
+            })?;
+            let id = graph.intern(name);
+            let edges = next.into_iter().map(|name| graph.intern(name)).collect();
+            if graph.edges[id as usize].replace(edges).is_some() {
+                return Err(ParseError::new(
+                    row,
+                    format!("node `{name}` is defined twice"),
+                ));
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Returns the id of the node, adding it if it is new.
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(&id) = self.ids.get(name) {
+            return id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), id);
+        self.edges.push(None);
+        id
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> {
+        self.ids.get(name).copied()
+    }
+
+    pub fn name(&self, id: u32) -> &str {
+        &self.names[id as usize]
+    }
+
+    /// Returns the node reached from `node` by the move at `index`.
+    ///
+    /// The node reached may be undefined, since a walk can still end there.
+    /// A walk must check it with `check_defined` before stepping from it.
+    pub fn step(&self, node: u32, index: usize) -> Result<u32, WalkError> {
+        // Walks only step from their start and from checked nodes.
+        let edges = self.edges[node as usize]
+            .as_ref()
+            .ok_or_else(|| WalkError::MissingStart(self.name(node).to_string()))?;
+        let Move(edge) = self.moves[index];
+        edges
+            .get(edge)
+            .copied()
+            .ok_or_else(|| WalkError::MissingEdge {
+                node: self.name(node).to_string(),
+                edge,
+            })
+    }
+
+    /// Checks that the walk can go on from `node`, which it reached from `from`.
+    fn check_defined(&self, from: u32, node: u32) -> Result<(), WalkError> {
+        match self.edges[node as usize] {
+            Some(_) => Ok(()),
+            None => Err(WalkError::Dangling {
+                from: self.name(from).to_string(),
+                to: self.name(node).to_string(),
+            }),
+        }
+    }
+
+    /// Returns the id of a start node, which must be defined.
+    fn start(&self, name: &str) -> Result<u32, WalkError> {
+        self.id(name)
+            .filter(|&id| self.edges[id as usize].is_some())
+            .ok_or_else(|| WalkError::MissingStart(name.to_string()))
+    }
+
+    /// Returns, for each node id, whether the node's name ends with `terminator`.
+    fn ends_with(&self, terminator: &str) -> Vec<bool> {
+        self.names
+            .iter()
+            .map(|name| name.ends_with(terminator))
+            .collect()
+    }
+}
+
+/// Splits a line like `AAA = (BBB, CCC)` into the node's name and the names it points at.
+fn parse_node(line: &str) -> Option<(&str, Vec<&str>)> {
+    let is_name = |name: &str| {
+        !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || "=(),".contains(c))
+    };
+    let (name, rest) = line.split_once('=')?;
+    let next: Vec<&str> = rest
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .split(',')
+        .map(str::trim)
+        .collect();
+    let name = name.trim();
+    (is_name(name) && next.iter().all(|name| is_name(name))).then_some((name, next))
+}
+
+/// The colors of the ghosts' subgraphs in a DOT export.
+const GHOST_COLORS: [&str; 8] = [
+    "red",
+    "blue",
+    "darkgreen",
+    "orange",
+    "purple",
+    "brown",
+    "deeppink",
+    "cyan3",
+];
+
+/// Writes the network in Graphviz DOT format.
+///
+/// Edges are labeled with their moves, `L` and `R` for nodes with two edges,
+/// and edges to the same node are merged into one. Start nodes are green,
+/// end nodes are red double circles, and nodes that are never defined are
+/// dashed. With `color_ghosts`, everything each ghost can reach is drawn in
+/// that ghost's color.
+pub fn to_dot(graph: &Graph, color_ghosts: bool) -> String {
+    let quote = |id: u32| {
+        format!(
+            "\"{}\"",
+            graph.name(id).replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    };
+
+    // The colors of the ghosts that can reach each node.
+    let mut colors: Vec<Vec<&str>> = vec![vec![]; graph.names.len()];
+    if color_ghosts {
+        let mut starts: Vec<u32> = (0..graph.names.len() as u32)
+            .filter(|&id| graph.name(id).ends_with('A'))
+            .collect();
+        starts.sort_unstable_by_key(|&id| graph.name(id));
+        for (start, color) in starts.into_iter().zip(GHOST_COLORS.iter().cycle()) {
+            let mut reached = HashSet::from([start]);
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                colors[node as usize].push(color);
+                for &next in graph.edges[node as usize].iter().flatten() {
+                    if reached.insert(next) {
+                        stack.push(next);
+                    }
                 }
             }
-            Self { moves, table }
-        } else {
-            panic!("Expected a least 1 line in input: `{}`", s);
         }
     }
+
+    let mut out = String::from("digraph network {\n");
+    for id in 0..graph.names.len() as u32 {
+        let name = graph.name(id);
+        let mut attributes = vec![];
+        if name.ends_with('A') {
+            attributes.push("style=filled, fillcolor=palegreen".to_string());
+        } else if name.ends_with('Z') {
+            attributes.push("shape=doublecircle, style=filled, fillcolor=lightpink".to_string());
+        } else if graph.edges[id as usize].is_none() {
+            attributes.push("style=dashed".to_string());
+        }
+        if let Some(color) = colors[id as usize].first() {
+            attributes.push(format!("color={color}, penwidth=2"));
+        }
+        out += &format!("    {} [{}];\n", quote(id), attributes.join(", "));
+    }
+
+    for id in 0..graph.names.len() as u32 {
+        let Some(edges) = &graph.edges[id as usize] else {
+            continue;
+        };
+        let mut labels: Vec<(u32, Vec<String>)> = vec![];
+        for (i, &next) in edges.iter().enumerate() {
+            let label = match (edges.len(), i) {
+                (2, 0) => "L".to_string(),
+                (2, 1) => "R".to_string(),
+                _ => i.to_string(),
+            };
+            match labels.iter_mut().find(|(target, _)| *target == next) {
+                Some((_, moves)) => moves.push(label),
+                None => labels.push((next, vec![label])),
+            }
+        }
+        for (next, moves) in labels {
+            let mut attributes = vec![format!("label=\"{}\"", moves.join(","))];
+            if !colors[id as usize].is_empty() {
+                attributes.push(format!("color=\"{}\"", colors[id as usize].join(":")));
+            }
+            out += &format!(
+                "    {} -> {} [{}];\n",
+                quote(id),
+                quote(next),
+                attributes.join(", ")
+            );
+        }
+    }
+    out += "}\n";
+    out
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        ParseError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Why a walk through the network fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalkError {
+    /// The start node is not defined.
+    MissingStart(String),
+    /// The walk goes round a cycle that never reaches the terminator.
+    Unreachable { start: String, terminator: String },
+    /// The walk follows an edge to a node that is not defined.
+    Dangling { from: String, to: String },
+    /// The walk makes a move that the node has no edge for.
+    MissingEdge { node: String, edge: usize },
+    /// The number of steps does not fit in a `u64`.
+    Overflow,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkError::MissingStart(name) => write!(f, "there is no start node `{name}`"),
+            WalkError::Unreachable { start, terminator } => {
+                write!(
+                    f,
+                    "no node ending with `{terminator}` can be reached from `{start}`"
+                )
+            }
+            WalkError::Dangling { from, to } => {
+                write!(
+                    f,
+                    "node `{from}` has an edge to `{to}`, which is never defined"
+                )
+            }
+            WalkError::MissingEdge { node, edge } => write!(f, "node `{node}` has no edge {edge}"),
+            WalkError::Overflow => write!(f, "the number of steps overflows u64"),
+        }
+    }
+}
+
+impl Error for WalkError {}
+
 #[cfg(test)]
 mod tests {
-    use super::{part_one, part_two};
+    use adventofcode2023::Rng;
+
+    use super::{
+        crt, find_cycle, first_common_hit, get_steps, ghost_cycles, lcm_shortcut_is_valid,
+        part_one, part_two, to_dot, Cycle, Graph, ParseError, WalkError,
+    };
 
     #[test]
     fn test_example_1() {
@@ -119,7 +614,7 @@ mod tests {
             "GGG = (GGG, GGG)\n",
             "ZZZ = (ZZZ, ZZZ)\n",
         );
-        assert_eq!(part_one(input), 2);
+        assert_eq!(part_one(input).unwrap(), 2);
     }
 
     #[test]
@@ -130,7 +625,7 @@ mod tests {
             "BBB = (AAA, ZZZ)\n",
             "ZZZ = (ZZZ, ZZZ)\n",
         );
-        assert_eq!(part_one(input), 6);
+        assert_eq!(part_one(input).unwrap(), 6);
     }
 
     #[test]
@@ -146,6 +641,281 @@ mod tests {
             "22Z = (22B, 22B)\n",
             "XXX = (XXX, XXX)\n",
         );
-        assert_eq!(part_two(input), 6);
+        assert_eq!(part_two(input).unwrap(), Some(6));
+        assert!(lcm_shortcut_is_valid(
+            &ghost_cycles(&Graph::parse(input).unwrap()).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_offset_cycles() {
+        // 11A is on 11Z after 1, 4, 7, ... steps and 22A on 22Z after 2, 4, 6, ... steps,
+        // so the LCM of the first hits, 2, is wrong.
+        let input = concat!(
+            "L\n",
+            "11A = (11Z, XXX)\n",
+            "11Z = (11B, XXX)\n",
+            "11B = (11C, XXX)\n",
+            "11C = (11Z, XXX)\n",
+            "22A = (22B, XXX)\n",
+            "22B = (22Z, XXX)\n",
+            "22Z = (22B, XXX)\n",
+            "XXX = (XXX, XXX)\n",
+        );
+        assert_eq!(part_two(input).unwrap(), Some(4));
+        assert!(!lcm_shortcut_is_valid(
+            &ghost_cycles(&Graph::parse(input).unwrap()).unwrap()
+        ));
+
+        // Odd steps only for one ghost and even steps only for the other.
+        let input = concat!(
+            "L\n",
+            "11A = (11B, XXX)\n",
+            "11B = (11Z, XXX)\n",
+            "11Z = (11B, XXX)\n",
+            "22A = (22Z, XXX)\n",
+            "22Z = (22B, XXX)\n",
+            "22B = (22Z, XXX)\n",
+            "XXX = (XXX, XXX)\n",
+        );
+        assert_eq!(part_two(input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // Two ghosts with coprime periods of about 2^40 only meet after about 2^80 steps.
+        let cycle = |period| Cycle {
+            prefix: 1,
+            period,
+            prefix_hits: vec![],
+            cycle_hits: vec![period],
+        };
+        let cycles = [cycle(1 << 40), cycle((1 << 40) - 1)];
+        assert_eq!(first_common_hit(&cycles), Err(WalkError::Overflow));
+        assert_eq!(
+            crt((0, 1 << 40), (0, (1 << 40) - 1)),
+            Err(WalkError::Overflow)
+        );
+
+        // The same periods with a common factor still fit.
+        let cycles = [cycle(1 << 40), cycle(1 << 41)];
+        assert_eq!(first_common_hit(&cycles), Ok(Some(1 << 41)));
+        assert_eq!(crt((3, 4), (5, 6)), Ok(Some((11, 12))));
+        assert_eq!(crt((3, 4), (4, 6)), Ok(None));
+    }
+
+    #[test]
+    fn test_cycles_brute_force() {
+        // Random networks with a few ghosts, against walking every ghost at once.
+        let mut rng = Rng::new(1);
+        let mut next = |n: usize| rng.below(n);
+        for _ in 0..300 {
+            let names: Vec<String> = (0..8)
+                .map(|i| format!("{i}{i}{}", ['A', 'B', 'C', 'Z'][i % 4]))
+                .collect();
+            // Half of the networks branch three ways.
+            let edges = 2 + next(2);
+            let moves: Vec<String> = (0..1 + next(4)).map(|_| next(edges).to_string()).collect();
+            let mut input = format!("{}\n\n", moves.join(" "));
+            for name in &names {
+                let next: Vec<&str> = (0..edges).map(|_| names[next(8)].as_str()).collect();
+                input += &format!("{name} = ({})\n", next.join(", "));
+            }
+
+            let graph = Graph::parse(&input).unwrap();
+            let mut ghosts: Vec<u32> = names
+                .iter()
+                .filter(|name| name.ends_with('A'))
+                .map(|name| graph.id(name).unwrap())
+                .collect();
+            let expected = (1..=10_000).find(|&steps| {
+                for ghost in &mut ghosts {
+                    *ghost = graph
+                        .step(*ghost, (steps - 1) as usize % graph.moves.len())
+                        .unwrap();
+                }
+                ghosts.iter().all(|&ghost| graph.name(ghost).ends_with('Z'))
+            });
+            assert_eq!(
+                first_common_hit(&ghost_cycles(&graph).unwrap()).unwrap(),
+                expected,
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_long_names() {
+        let input = concat!(
+            "LR\n",
+            "\n",
+            "START = (middle, START)\n",
+            "middle = (x, END)\n",
+            "x = (x, x)\n",
+            "END = (END, END)\n",
+        );
+        let graph = Graph::parse(input).unwrap();
+        assert_eq!(get_steps(&graph, "START", "END"), Ok(2));
+        assert_eq!(graph.name(graph.id("middle").unwrap()), "middle");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input| Graph::parse(input).err().unwrap();
+        assert_eq!(
+            error("LR\n\nAAA = (BBB, CCC)\nBBB = BBB, CCC\n"),
+            ParseError {
+                line: 4,
+                message: "expected `NAME = (NEXT, ...)`, found `BBB = BBB, CCC`".to_string()
+            }
+        );
+        assert_eq!(error("LR\nAAA = (, CCC)\n").line, 2);
+        assert_eq!(error("LR\nA A = (B, C)\n").line, 2);
+        assert_eq!(
+            error("LR\nAAA = (B, C)\nAAA = (C, B)\n").to_string(),
+            "line 3: node `AAA` is defined twice"
+        );
+        assert_eq!(
+            error("LXR\nAAA = (B, C)\n").to_string(),
+            "line 1: invalid move `X`"
+        );
+        assert_eq!(
+            error("\n\n").to_string(),
+            "line 1: expected a line of moves"
+        );
+    }
+
+    #[test]
+    fn test_branching() {
+        let input = concat!(
+            "2 0 1\n",
+            "\n",
+            "AAA = (BBB, CCC, DDD)\n",
+            "BBB = (AAA, ZZZ, BBB)\n",
+            "CCC = (ZZZ, ZZZ, ZZZ)\n",
+            "DDD = (BBB, AAA, CCC)\n",
+            "ZZZ = (ZZZ, ZZZ, ZZZ)\n",
+        );
+        // AAA -2-> DDD -0-> BBB -1-> ZZZ
+        assert_eq!(part_one(input).unwrap(), 3);
+        let letters = input.replacen("2 0 1", "cab", 1);
+        assert_eq!(part_one(&letters).unwrap(), 3);
+        assert_eq!(part_one(&input.replacen("2 0 1", "2,0,1", 1)).unwrap(), 3);
+
+        // A node that is a dead end.
+        let input = concat!("b\n", "AAA = (ZZZ)\n", "ZZZ = (ZZZ)\n");
+        let graph = Graph::parse(input).unwrap();
+        assert_eq!(
+            get_steps(&graph, "AAA", "ZZZ"),
+            Err(WalkError::MissingEdge {
+                node: "AAA".to_string(),
+                edge: 1
+            })
+        );
+        assert_eq!(part_one(&input.replacen('b', "a", 1)).unwrap(), 1);
+
+        assert_eq!(
+            Graph::parse("0 x\nAAA = (ZZZ)\n")
+                .err()
+                .unwrap()
+                .to_string(),
+            "line 1: invalid move `x`"
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let input = concat!(
+            "LR\n",
+            "11A = (11Z, XXX)\n",
+            "11Z = (11Z, \"q\")\n",
+            "XXX = (XXX, XXX)\n",
+        );
+        let graph = Graph::parse(input).unwrap();
+        let expected = concat!(
+            "digraph network {\n",
+            "    \"11A\" [style=filled, fillcolor=palegreen];\n",
+            "    \"11Z\" [shape=doublecircle, style=filled, fillcolor=lightpink];\n",
+            "    \"XXX\" [];\n",
+            "    \"\\\"q\\\"\" [style=dashed];\n",
+            "    \"11A\" -> \"11Z\" [label=\"L\"];\n",
+            "    \"11A\" -> \"XXX\" [label=\"R\"];\n",
+            "    \"11Z\" -> \"11Z\" [label=\"L\"];\n",
+            "    \"11Z\" -> \"\\\"q\\\"\" [label=\"R\"];\n",
+            "    \"XXX\" -> \"XXX\" [label=\"L,R\"];\n",
+            "}\n",
+        );
+        assert_eq!(to_dot(&graph, false), expected);
+
+        let input = concat!(
+            "LR\n",
+            "11A = (11B, 11B)\n",
+            "11B = (XXX, XXX)\n",
+            "22A = (XXX, XXX)\n",
+            "XXX = (XXX, XXX)\n",
+        );
+        let dot = to_dot(&Graph::parse(input).unwrap(), true);
+        assert!(dot.contains("\"11B\" [color=red, penwidth=2];"));
+        assert!(
+            dot.contains("\"22A\" [style=filled, fillcolor=palegreen, color=blue, penwidth=2];")
+        );
+        assert!(dot.contains("\"XXX\" -> \"XXX\" [label=\"L,R\", color=\"red:blue\"];"));
+    }
+
+    #[test]
+    fn test_walk_errors() {
+        let input = concat!(
+            "LR\n",
+            "AAA = (BBB, AAA)\n",
+            "BBB = (AAA, BBB)\n",
+            "CCC = (DDD, ZZZ)\n",
+            "ZZZ = (ZZZ, ZZZ)\n",
+        );
+        let graph = Graph::parse(input).unwrap();
+        assert_eq!(
+            get_steps(&graph, "AAA", "ZZZ"),
+            Err(WalkError::Unreachable {
+                start: "AAA".to_string(),
+                terminator: "ZZZ".to_string()
+            })
+        );
+        assert_eq!(
+            get_steps(&graph, "QQQ", "ZZZ"),
+            Err(WalkError::MissingStart("QQQ".to_string()))
+        );
+        assert_eq!(
+            get_steps(&graph, "DDD", "ZZZ"),
+            Err(WalkError::MissingStart("DDD".to_string()))
+        );
+        let error = get_steps(&graph, "CCC", "ZZZ").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "node `CCC` has an edge to `DDD`, which is never defined"
+        );
+        // Reaching an undefined node is fine if the walk ends there.
+        assert_eq!(get_steps(&graph, "CCC", "DDD"), Ok(1));
+
+        assert_eq!(
+            part_one("LR\nBBB = (BBB, BBB)\n").unwrap_err().to_string(),
+            "there is no start node `AAA`"
+        );
+        assert_eq!(part_two(input).unwrap(), None);
+
+        // The error names the node the walk came from, not just any node
+        // with an edge to the undefined one.
+        let input = concat!(
+            "L\n",
+            "CCC = (QQQ, QQQ)\n",
+            "AAA = (BBB, BBB)\n",
+            "BBB = (QQQ, QQQ)\n",
+        );
+        let graph = Graph::parse(input).unwrap();
+        let dangling = |from: &str| WalkError::Dangling {
+            from: from.to_string(),
+            to: "QQQ".to_string(),
+        };
+        assert_eq!(get_steps(&graph, "AAA", "ZZZ"), Err(dangling("BBB")));
+        assert_eq!(get_steps(&graph, "CCC", "ZZZ"), Err(dangling("CCC")));
+        assert_eq!(find_cycle(&graph, "AAA", "Z"), Err(dangling("BBB")));
     }
 }
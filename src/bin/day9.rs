@@ -1,74 +1,318 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
 use std::fs;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-fn main() {
+use num::{BigInt, BigRational, One, Signed, Zero};
+
+fn main() -> anyhow::Result<()> {
     let input = fs::read_to_string("inputs/9.txt").expect("Failed to read input file");
-    println!("Part one: {}", part_one(&input));
-    println!("Part two: {}", part_two(&input));
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [] => {
+            println!("Part one: {}", part_one(&input)?);
+            println!("Part two: {}", part_two(&input)?);
+        }
+        ["--lenient"] => {
+            let (sequences, skipped) = parse_sequences_lenient(&input);
+            for error in skipped {
+                eprintln!("Skipped {error}");
+            }
+            println!("Part one: {}", sum_extrapolated(&sequences, 1));
+            println!("Part two: {}", sum_extrapolated(&sequences, -1));
+        }
+        ["--explain"] => {
+            for (i, line) in input.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let sequence = Sequence::parse(line, i + 1)?;
+                match sequence.polynomial() {
+                    Ok(polynomial) => println!(
+                        "line {}: f(x) = {polynomial}, so f(-1) = {} and f({}) = {}",
+                        i + 1,
+                        sequence.extrapolate(-1),
+                        sequence.values().len(),
+                        sequence.extrapolate(1)
+                    ),
+                    Err(error) => println!("line {}: {error}", i + 1),
+                }
+            }
+        }
+        _ => return Err(anyhow::anyhow!("usage: day9 [--lenient | --explain]")),
+    }
+    Ok(())
+}
+
+fn part_one(input: &str) -> Result<BigInt, ParseError> {
+    Ok(sum_extrapolated(&parse_sequences(input)?, 1))
+}
+
+fn part_two(input: &str) -> Result<BigInt, ParseError> {
+    Ok(sum_extrapolated(&parse_sequences(input)?, -1))
 }
 
-fn part_one(input: &str) -> i64 {
-    let sequences: Vec<_> = input.lines().filter_map(|line| {
-        Some(line.split_whitespace().map(|s| s.parse::<i64>().ok()).collect::<Option<Vec<_>>>()?)
-    }).collect();
+/// Sums the extrapolated values exactly, since they can grow far past `i64`.
+fn sum_extrapolated(sequences: &[Sequence], k: i64) -> BigInt {
+    sequences
+        .iter()
+        .map(|sequence| sequence.extrapolate(k))
+        .sum()
+}
 
-    let mut sum = 0;
+/// Parses one history per line, failing on the first invalid number.
+///
+/// Blank lines are not histories and are skipped.
+pub fn parse_sequences(input: &str) -> Result<Vec<Sequence>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| Sequence::parse(line, i + 1))
+        .collect()
+}
 
-    for sequence in sequences {
-        let mut diffs = vec![sequence];
-        loop {
-            let diff: Vec<_> = diffs.last().unwrap().windows(2).map(|xs| xs[1] - xs[0]).collect();
-            diffs.push(diff.clone());
-            if diff.iter().all(|&x| x == 0) {
-                break;
+/// Parses one history per line like `parse_sequences`, but skips the lines
+/// with an invalid number and returns an error for each of them.
+pub fn parse_sequences_lenient(input: &str) -> (Vec<Sequence>, Vec<ParseError>) {
+    let mut sequences = vec![];
+    let mut skipped = vec![];
+    for (i, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Sequence::parse(line, i + 1) {
+            Ok(sequence) => sequences.push(sequence),
+            Err(error) => skipped.push(error),
+        }
+    }
+    (sequences, skipped)
+}
+
+/// A history, seen as the values of a polynomial at `x = 0, 1, 2, ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    values: Vec<i64>,
+    /// The first value of each row of the difference pyramid,
+    /// i.e. the forward differences `Δ^j f(0)`, up to the last nonzero one.
+    differences: Vec<BigInt>,
+}
+
+impl Sequence {
+    /// Creates a sequence from its values.
+    ///
+    /// An empty sequence is the zero polynomial, so it extrapolates to 0.
+    /// A sequence with one value is constant, so it extrapolates to that value.
+    /// Neither has enough values to confirm its polynomial.
+    pub fn new(values: Vec<i64>) -> Self {
+        let mut row: Vec<BigInt> = values.iter().map(|&value| BigInt::from(value)).collect();
+        let mut differences = vec![];
+        while !row.iter().all(Zero::is_zero) {
+            differences.push(row[0].clone());
+            row = row.windows(2).map(|xs| &xs[1] - &xs[0]).collect();
+        }
+        Sequence {
+            values,
+            differences,
+        }
+    }
+
+    /// Parses the numbers on the `line`th line of the input.
+    pub fn parse(s: &str, line: usize) -> Result<Self, ParseError> {
+        let values = s
+            .split_whitespace()
+            .map(|token| {
+                token.parse().map_err(|error: ParseIntError| ParseError {
+                    line,
+                    token: token.to_string(),
+                    reason: error.to_string(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Sequence::new(values))
+    }
+
+    pub fn values(&self) -> &[i64] {
+        &self.values
+    }
+
+    /// Returns the value `k` places after the last value, or for a negative `k`,
+    /// `-k` places before the first. `extrapolate(0)` is the last value itself.
+    pub fn extrapolate(&self, k: i64) -> BigInt {
+        if k >= 0 {
+            self.value_at(BigInt::from(self.values.len()) - 1 + k)
+        } else {
+            self.value_at(BigInt::from(k))
+        }
+    }
+
+    /// Returns the value at `x`, where the first value is at `x = 0`.
+    ///
+    /// Newton's forward-difference formula gives `f(x) = Σ C(x, j) Δ^j f(0)`,
+    /// which holds for every integer `x`, negative ones included, with the
+    /// generalized binomial coefficient `C(x, j) = x (x - 1) ... (x - j + 1) / j!`.
+    /// Each coefficient follows exactly from the previous one, as
+    /// `C(x, j) = C(x, j - 1) (x - j + 1) / j`, so everything stays an integer.
+    pub fn value_at(&self, x: BigInt) -> BigInt {
+        let mut value = BigInt::zero();
+        let mut binomial = BigInt::from(1);
+        for (j, difference) in self.differences.iter().enumerate() {
+            if j > 0 {
+                binomial = binomial * (&x - (j - 1)) / j;
             }
+            value += &binomial * difference;
         }
+        value
+    }
 
-        diffs.last_mut().unwrap().push(0);
-        
-        for i in (1..diffs.len()).rev() {
-            let n = diffs[i].last().unwrap() + diffs[i - 1].last().unwrap();
-            diffs[i - 1].push(n); 
+    /// Recovers the polynomial that generates the sequence.
+    ///
+    /// A polynomial of degree `d` is only confirmed if the sequence has at
+    /// least `d + 2` values, so that the difference pyramid ends in a row
+    /// of zeros. Otherwise any polynomial of degree `n - 1` or more fits.
+    pub fn polynomial(&self) -> Result<Polynomial, NotPolynomial> {
+        if self.differences.len() >= self.values.len() {
+            return Err(NotPolynomial {
+                len: self.values.len(),
+            });
         }
 
-        sum += diffs.first().unwrap().last().unwrap();
+        // Newton's form, `Σ Δ^j f(0) x (x - 1) ... (x - j + 1) / j!`,
+        // expanded one falling factorial at a time.
+        let mut coefficients = vec![BigRational::zero(); self.differences.len()];
+        let mut falling = vec![BigInt::one()];
+        let mut factorial = BigInt::one();
+        for (j, difference) in self.differences.iter().enumerate() {
+            if j > 0 {
+                // Multiply by `x - (j - 1)`.
+                let shifted = BigInt::from(j - 1);
+                let mut next = vec![BigInt::zero(); falling.len() + 1];
+                for (power, coefficient) in falling.iter().enumerate() {
+                    next[power + 1] += coefficient;
+                    next[power] -= coefficient * &shifted;
+                }
+                falling = next;
+                factorial *= j;
+            }
+            for (power, coefficient) in falling.iter().enumerate() {
+                coefficients[power] +=
+                    BigRational::new(coefficient * difference, factorial.clone());
+            }
+        }
+        Ok(Polynomial { coefficients })
     }
+}
 
-    sum
+/// A polynomial with exact rational coefficients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    /// The coefficient of each power of `x`, starting from the constant.
+    /// The last one is never zero, so the zero polynomial has none.
+    coefficients: Vec<BigRational>,
 }
 
-fn part_two(input: &str) -> i64 {
-    let sequences: Vec<_> = input.lines().filter_map(|line| {
-        Some(line.split_whitespace().map(|s| s.parse::<i64>().ok()).collect::<Option<Vec<_>>>()?)
-    }).collect();
+impl Polynomial {
+    pub fn coefficients(&self) -> &[BigRational] {
+        &self.coefficients
+    }
 
-    let mut sum = 0;
+    /// Returns the degree, counting the zero polynomial as degree 0.
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+}
 
-    for sequence in sequences {
-        let mut diffs = vec![sequence.clone()];
-        loop {
-            let diff: Vec<_> = diffs.last().unwrap().windows(2).map(|xs| xs[1] - xs[0]).collect();
-            diffs.push(diff.clone());
-            if diff.iter().all(|&x| x == 0) {
-                break;
+/// Writes the polynomial like `x^2/2 + 3x/2 + 1`.
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.coefficients.is_empty() {
+            return write!(f, "0");
+        }
+        let terms = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, c)| !c.is_zero());
+        for (i, (power, coefficient)) in terms.enumerate() {
+            match (i, coefficient.is_negative()) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let numerator = coefficient.numer().abs();
+            if power == 0 || !numerator.is_one() {
+                write!(f, "{numerator}")?;
+            }
+            match power {
+                0 => {}
+                1 => write!(f, "x")?,
+                _ => write!(f, "x^{power}")?,
+            }
+            if !coefficient.denom().is_one() {
+                write!(f, "/{}", coefficient.denom())?;
             }
         }
+        Ok(())
+    }
+}
 
-        diffs.last_mut().unwrap().insert(0, 0);
-        
-        for i in (1..diffs.len()).rev() {
-            let n = diffs[i - 1].first().unwrap() - diffs[i].first().unwrap();
-            diffs[i - 1].insert(0, n); 
-        }
+/// A sequence whose difference pyramid runs out before reaching a row of zeros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotPolynomial {
+    pub len: usize,
+}
 
-        sum += diffs.first().unwrap().first().unwrap();
+impl fmt::Display for NotPolynomial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "not polynomial of degree < {}",
+            self.len.saturating_sub(1)
+        )
     }
+}
+
+impl Error for NotPolynomial {}
+
+impl FromStr for Sequence {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Sequence::parse(s, 1)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub token: String,
+    pub reason: String,
+}
 
-    sum
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}: invalid number `{}`: {}",
+            self.line, self.token, self.reason
+        )
+    }
 }
 
+impl Error for ParseError {}
+
 #[cfg(test)]
 mod tests {
-    use super::{part_one, part_two};
+    use num::BigInt;
+
+    use super::{
+        parse_sequences, parse_sequences_lenient, part_one, part_two, NotPolynomial, ParseError,
+        Sequence,
+    };
 
     #[test]
     fn test_example() {
@@ -77,7 +321,118 @@ mod tests {
             "1 3 6 10 15 21\n",
             "10 13 16 21 30 45\n",
         );
-        assert_eq!(part_one(input), 114);
-        assert_eq!(part_two(input), 2);
+        assert_eq!(part_one(input).unwrap(), BigInt::from(114));
+        assert_eq!(part_two(input).unwrap(), BigInt::from(2));
+    }
+
+    #[test]
+    fn test_extrapolate() {
+        let sequence: Sequence = "10 13 16 21 30 45".parse().unwrap();
+        // The pyramid extended three steps in both directions.
+        let expected = [-19, -4, 5, 10, 13, 16, 21, 30, 45, 68, 101, 146];
+        for (x, &value) in (-3..).zip(&expected) {
+            assert_eq!(
+                sequence.value_at(BigInt::from(x)),
+                BigInt::from(value),
+                "x = {x}"
+            );
+        }
+        assert_eq!(sequence.extrapolate(1), BigInt::from(68));
+        assert_eq!(sequence.extrapolate(3), BigInt::from(146));
+        assert_eq!(sequence.extrapolate(0), BigInt::from(45));
+        assert_eq!(sequence.extrapolate(-1), BigInt::from(5));
+        assert_eq!(sequence.extrapolate(-3), BigInt::from(-19));
+    }
+
+    #[test]
+    fn test_extrapolate_far() {
+        // The squares, far beyond anything an i64 holds.
+        let sequence: Sequence = "0 1 4 9 16".parse().unwrap();
+        let k = 1_000_000_000_000_000_000;
+        let x = BigInt::from(4 + k);
+        assert_eq!(sequence.extrapolate(k), &x * &x);
+        assert_eq!(sequence.extrapolate(-k), BigInt::from(k) * k);
+
+        // The sum of values that each fit in an i64 does not.
+        let input = "0 4611686018427387904\n0 4611686018427387904\n";
+        assert_eq!(part_one(input).unwrap(), BigInt::from(1u64 << 63) * 2);
+        assert_eq!(part_two(input).unwrap(), BigInt::from(i64::MIN));
+    }
+
+    #[test]
+    fn test_short_sequences() {
+        assert_eq!(Sequence::new(vec![]).extrapolate(1), BigInt::from(0));
+        assert_eq!(Sequence::new(vec![7]).extrapolate(-5), BigInt::from(7));
+        assert_eq!(Sequence::new(vec![0, 0, 0]).extrapolate(9), BigInt::from(0));
+    }
+
+    #[test]
+    fn test_polynomial() {
+        let polynomial = |s: &str| {
+            s.parse::<Sequence>()
+                .unwrap()
+                .polynomial()
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(polynomial("0 3 6 9 12 15"), "3x");
+        assert_eq!(polynomial("1 3 6 10 15 21"), "x^2/2 + 3x/2 + 1");
+        assert_eq!(polynomial("10 13 16 21 30 45"), "x^3/3 - x^2 + 11x/3 + 10");
+        assert_eq!(polynomial("1 0 -3 -8"), "-x^2 + 1");
+        assert_eq!(polynomial("-2 -2 -2"), "-2");
+        assert_eq!(polynomial("0 0"), "0");
+
+        let sequence: Sequence = "10 13 16 21 30 45".parse().unwrap();
+        assert_eq!(sequence.polynomial().unwrap().degree(), 3);
+    }
+
+    #[test]
+    fn test_not_polynomial() {
+        let error = "1 2 4 8 16"
+            .parse::<Sequence>()
+            .unwrap()
+            .polynomial()
+            .unwrap_err();
+        assert_eq!(error, NotPolynomial { len: 5 });
+        // A line can only be a polynomial of degree 3 if it has a fifth value to confirm it.
+        assert_eq!(error.to_string(), "not polynomial of degree < 4");
+        assert!("1 2 4 8".parse::<Sequence>().unwrap().polynomial().is_err());
+        assert!("7".parse::<Sequence>().unwrap().polynomial().is_err());
+        assert!("7 7".parse::<Sequence>().unwrap().polynomial().is_ok());
+    }
+
+    #[test]
+    fn test_strict_parsing() {
+        let input = "0 3 6 9\n\n1 3 6 l0 15\n1 2 3 99999999999999999999\n";
+        assert_eq!(
+            parse_sequences(input).unwrap_err(),
+            ParseError {
+                line: 3,
+                token: "l0".to_string(),
+                reason: "invalid digit found in string".to_string(),
+            }
+        );
+        assert_eq!(
+            part_one(input).unwrap_err().to_string(),
+            "line 3: invalid number `l0`: invalid digit found in string"
+        );
+
+        let (sequences, skipped) = parse_sequences_lenient(input);
+        assert_eq!(sequences, vec![Sequence::new(vec![0, 3, 6, 9])]);
+        let lines: Vec<_> = skipped
+            .iter()
+            .map(|error| (error.line, error.token.as_str()))
+            .collect();
+        assert_eq!(lines, [(3, "l0"), (4, "99999999999999999999")]);
+    }
+
+    #[test]
+    fn test_empty_and_single() {
+        // Blank lines are skipped, single numbers are constant.
+        let input = "\n   \n7\n-3\n\n";
+        assert_eq!(parse_sequences(input).unwrap().len(), 2);
+        assert_eq!(part_one(input).unwrap(), BigInt::from(4));
+        assert_eq!(part_two(input).unwrap(), BigInt::from(4));
+        assert_eq!(part_one("").unwrap(), BigInt::from(0));
     }
 }
@@ -0,0 +1,7 @@
+//! Code that is shared between the solutions for each day.
+
+pub mod range_set;
+pub mod rng;
+
+pub use range_set::RangeSet;
+pub use rng::Rng;
@@ -0,0 +1,335 @@
+use std::ops::{Add, Range, Sub};
+
+/// A set of numbers stored as a sorted list of ranges.
+///
+/// The ranges are always normalized: they are never empty, and ranges
+/// that overlap or touch are merged, so there is exactly one way to
+/// store any set.
+///
+/// ## Examples
+///
+/// ```
+/// use adventofcode2023::RangeSet;
+///
+/// let mut set = RangeSet::new();
+/// set.insert(0..5);
+/// set.insert(3..8);
+/// set.insert(10..12);
+/// assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![0..8, 10..12]);
+/// assert_eq!(set.total_len(), 10);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RangeSet<T> {
+    ranges: Vec<Range<T>>,
+}
+
+impl<T> Default for RangeSet<T> {
+    fn default() -> Self {
+        Self { ranges: Vec::new() }
+    }
+}
+
+impl<T: Copy + Ord> RangeSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds every number in the range to the set.
+    pub fn insert(&mut self, range: Range<T>) {
+        if range.is_empty() {
+            return;
+        }
+        // Every range from `first` up to `last` overlaps or touches the new one.
+        let first = self.ranges.partition_point(|r| r.end < range.start);
+        let last = self.ranges.partition_point(|r| r.start <= range.end);
+        if first == last {
+            self.ranges.insert(first, range);
+            return;
+        }
+        let start = range.start.min(self.ranges[first].start);
+        let end = range.end.max(self.ranges[last - 1].end);
+        self.ranges.splice(first..last, [start..end]);
+    }
+
+    /// Adds every number in the other set to this one.
+    pub fn merge(&mut self, other: &RangeSet<T>) {
+        for range in &other.ranges {
+            self.insert(range.clone());
+        }
+    }
+
+    /// The numbers that are in either set.
+    pub fn union(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result = self.clone();
+        result.merge(other);
+        result
+    }
+
+    /// The numbers that are in both sets.
+    pub fn intersection(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (&self.ranges[i], &other.ranges[j]);
+            let overlap = a.start.max(b.start)..a.end.min(b.end);
+            if !overlap.is_empty() {
+                result.push(overlap);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self { ranges: result }
+    }
+
+    /// The numbers that are in this set, but not in the other.
+    pub fn difference(&self, other: &RangeSet<T>) -> RangeSet<T> {
+        let mut result = Vec::new();
+        let mut j = 0;
+        for range in &self.ranges {
+            let mut start = range.start;
+            // Skip the ranges that end before this one starts.
+            while j < other.ranges.len() && other.ranges[j].end <= start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < range.end {
+                if start < other.ranges[k].start {
+                    result.push(start..other.ranges[k].start);
+                }
+                start = start.max(other.ranges[k].end);
+                k += 1;
+            }
+            if start < range.end {
+                result.push(start..range.end);
+            }
+        }
+        Self { ranges: result }
+    }
+
+    pub fn contains(&self, x: &T) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= *x);
+        self.ranges.get(i).is_some_and(|r| r.contains(x))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The ranges in the set, in increasing order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Range<T>> {
+        self.ranges.iter()
+    }
+
+    /// The smallest number in the set.
+    pub fn first(&self) -> Option<T> {
+        self.ranges.first().map(|r| r.start)
+    }
+
+    /// Cuts the ranges of this set wherever a range of the other set
+    /// starts or ends, so that each piece is either entirely inside or
+    /// entirely outside of the other set.
+    pub fn split(&self, other: &RangeSet<T>) -> Vec<Range<T>> {
+        let mut result = Vec::new();
+        let mut j = 0;
+        for range in &self.ranges {
+            while j < other.ranges.len() && other.ranges[j].end <= range.start {
+                j += 1;
+            }
+            let mut start = range.start;
+            let mut k = j;
+            while k < other.ranges.len() && other.ranges[k].start < range.end {
+                for cut in [other.ranges[k].start, other.ranges[k].end] {
+                    if start < cut && cut < range.end {
+                        result.push(start..cut);
+                        start = cut;
+                    }
+                }
+                k += 1;
+            }
+            result.push(start..range.end);
+        }
+        result
+    }
+}
+
+impl<T: Copy + Ord + Default + Add<Output = T> + Sub<Output = T>> RangeSet<T> {
+    /// The amount of numbers in the set.
+    pub fn total_len(&self) -> T {
+        self.ranges
+            .iter()
+            .fold(T::default(), |acc, r| acc + (r.end - r.start))
+    }
+}
+
+impl<T: Copy + Ord> From<Range<T>> for RangeSet<T> {
+    fn from(range: Range<T>) -> Self {
+        let mut result = Self::new();
+        result.insert(range);
+        result
+    }
+}
+
+impl<T: Copy + Ord> FromIterator<Range<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        let mut result = Self::new();
+        for range in iter {
+            result.insert(range);
+        }
+        result
+    }
+}
+
+impl<T: Copy + Ord> Extend<Range<T>> for RangeSet<T> {
+    fn extend<I: IntoIterator<Item = Range<T>>>(&mut self, iter: I) {
+        for range in iter {
+            self.insert(range);
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a RangeSet<T> {
+    type Item = &'a Range<T>;
+    type IntoIter = std::slice::Iter<'a, Range<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ranges.iter()
+    }
+}
+
+impl<T> IntoIterator for RangeSet<T> {
+    type Item = Range<T>;
+    type IntoIter = std::vec::IntoIter<Range<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ranges.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RangeSet;
+    use crate::Rng;
+    use std::ops::Range;
+
+    /// Every property is checked against a plain bit mask over `0..64`.
+    const N: i64 = 64;
+
+    /// A few random ranges inside `0..N`.
+    fn ranges(rng: &mut Rng) -> Vec<Range<i64>> {
+        let mut below = |n: i64| rng.below(n as usize) as i64;
+        (0..below(6))
+            .map(|_| {
+                let start = below(N);
+                start..start + below(N - start + 1)
+            })
+            .collect()
+    }
+
+    fn mask(ranges: &[Range<i64>]) -> u64 {
+        ranges
+            .iter()
+            .flat_map(|r| r.clone())
+            .fold(0, |acc, x| acc | 1 << x)
+    }
+
+    fn set_mask(set: &RangeSet<i64>) -> u64 {
+        mask(&set.iter().cloned().collect::<Vec<_>>())
+    }
+
+    fn is_normalized(set: &RangeSet<i64>) -> bool {
+        set.iter().all(|r| !r.is_empty())
+            && set
+                .ranges
+                .windows(2)
+                .all(|pair| pair[0].end < pair[1].start)
+    }
+
+    /// Runs the property on many pairs of random sets, along with
+    /// the bit masks of the numbers in each one.
+    fn check(property: impl Fn(&RangeSet<i64>, u64, &RangeSet<i64>, u64)) {
+        let mut rng = Rng::new(1);
+        for _ in 0..2000 {
+            let (a, b) = (ranges(&mut rng), ranges(&mut rng));
+            let set_a: RangeSet<i64> = a.iter().cloned().collect();
+            let set_b: RangeSet<i64> = b.iter().cloned().collect();
+            property(&set_a, mask(&a), &set_b, mask(&b));
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        check(|a, mask_a, _, _| {
+            assert!(is_normalized(a), "{a:?}");
+            assert_eq!(mask_a, set_mask(a));
+            assert_eq!(mask_a.count_ones() as i64, a.total_len());
+            assert_eq!(mask_a == 0, a.is_empty());
+            let first = (mask_a != 0).then(|| mask_a.trailing_zeros() as i64);
+            assert_eq!(first, a.first());
+        });
+    }
+
+    #[test]
+    fn test_union() {
+        check(|a, mask_a, b, mask_b| {
+            let union = a.union(b);
+            assert!(is_normalized(&union));
+            assert_eq!(mask_a | mask_b, set_mask(&union));
+            assert_eq!(union, b.union(a));
+        });
+    }
+
+    #[test]
+    fn test_intersection() {
+        check(|a, mask_a, b, mask_b| {
+            let intersection = a.intersection(b);
+            assert!(is_normalized(&intersection), "{intersection:?}");
+            assert_eq!(mask_a & mask_b, set_mask(&intersection));
+        });
+    }
+
+    #[test]
+    fn test_difference() {
+        check(|a, mask_a, b, mask_b| {
+            let difference = a.difference(b);
+            assert!(is_normalized(&difference), "{difference:?}");
+            assert_eq!(mask_a & !mask_b, set_mask(&difference));
+        });
+    }
+
+    #[test]
+    fn test_contains() {
+        check(|a, mask_a, _, _| {
+            for x in -1..=N {
+                let expected = (0..N).contains(&x) && mask_a & 1 << x != 0;
+                assert_eq!(expected, a.contains(&x), "{x} in {a:?}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_split() {
+        check(|a, mask_a, b, mask_b| {
+            let pieces = a.split(b);
+            assert_eq!(mask_a, mask(&pieces));
+            assert!(pieces.windows(2).all(|pair| pair[0].end <= pair[1].start));
+            for piece in &pieces {
+                assert!(!piece.is_empty());
+                let piece_mask = mask(std::slice::from_ref(piece));
+                let inside = piece_mask & mask_b;
+                assert!(inside == 0 || inside == piece_mask, "{piece:?} in {b:?}");
+            }
+        });
+    }
+
+    #[test]
+    fn test_iter_in_order() {
+        let set: RangeSet<i64> = [20..30, -5..0, 0..3, 10..12].into_iter().collect();
+        assert_eq!(
+            vec![-5..3, 10..12, 20..30],
+            set.into_iter().collect::<Vec<_>>()
+        );
+    }
+}
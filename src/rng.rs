@@ -0,0 +1,44 @@
+/// A small seeded pseudo-random number generator (xorshift64*),
+/// so that a seed always produces the same numbers.
+///
+/// This is only meant for generating puzzles and test inputs,
+/// and is not suitable for anything that needs real randomness.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Self(seed.wrapping_mul(0x9E3779B97F4A7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545F4914F6CDD1D)
+    }
+
+    /// A random number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rng;
+
+    #[test]
+    fn test_seeded() {
+        let numbers = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..100).map(|_| rng.below(10)).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(7), numbers(7));
+        assert_ne!(numbers(7), numbers(8));
+        assert!(numbers(0).iter().all(|&x| x < 10));
+        // Every value turns up eventually.
+        assert!((0..10).all(|x| numbers(0).contains(&x)));
+    }
+}